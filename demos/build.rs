@@ -0,0 +1,38 @@
+//! Converts the palette files in `palettes/` into `Table` consts, one per file
+//! and named after it, which `palette.rs` includes as the `tables` module.
+
+use std::fmt::Write;
+use std::path::Path;
+
+#[path = "build/palette_file.rs"]
+mod palette_file;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build/palette_file.rs");
+    println!("cargo:rerun-if-changed=palettes");
+
+    let mut paths: Vec<_> = std::fs::read_dir("palettes")
+        .expect("can't read palettes/")
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+
+    let mut out = String::new();
+    for path in paths {
+        let (Some(stem), Some(extension)) = (path.file_stem(), path.extension()) else { continue };
+        let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let lut = palette_file::parse(&extension.to_string_lossy(), &text)
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+        let name = stem.to_string_lossy().to_uppercase().replace(['-', ' '], "_");
+        writeln!(out, "/// Generated from `{}`.", path.display()).unwrap();
+        writeln!(out, "pub const {}: Table = Table([", name).unwrap();
+        for (r, g, b) in lut {
+            writeln!(out, "    ({}, {}, {}),", r, g, b).unwrap();
+        }
+        writeln!(out, "]);").unwrap();
+    }
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(Path::new(&out_dir).join("palettes.rs"), out).unwrap();
+}
@@ -0,0 +1,97 @@
+//! Parsing of GIMP (`.gpl`) and Fractint (`.map`) palette files into 256-entry
+//! LUTs, shared by `build.rs` and the tests.
+
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error {
+    /// The 1-based line at fault, or `None` for the file as a whole.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+fn error(line: Option<usize>, message: impl Into<String>) -> Error {
+    Error { line, message: message.into() }
+}
+
+pub type Lut = [(u8, u8, u8); 256];
+
+/// Parses a palette file, choosing the format by `extension`.
+pub fn parse(extension: &str, text: &str) -> Result<Lut, Error> {
+    match extension {
+        "gpl" => parse_gpl(text),
+        "map" => parse_map(text),
+        _ => Err(error(None, format!("unknown palette format {:?}, expected gpl or map", extension))),
+    }
+}
+
+/// A GIMP palette: a "GIMP Palette" header, optional "Name:" and "Columns:"
+/// lines, then one "R G B [name]" line per colour. Lines starting with '#'
+/// are comments.
+pub fn parse_gpl(text: &str) -> Result<Lut, Error> {
+    let mut lines = text.lines().enumerate().map(|(n, line)| (n + 1, line.trim()));
+    match lines.next() {
+        Some((_, "GIMP Palette")) => {}
+        _ => return Err(error(Some(1), "expected a \"GIMP Palette\" header")),
+    }
+    let mut colours = Vec::new();
+    for (n, line) in lines {
+        if line.is_empty() || line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+            continue;
+        }
+        colours.push(parse_rgb(n, line)?);
+    }
+    resample(&colours)
+}
+
+/// A Fractint map: one "R G B [comment]" line per colour.
+pub fn parse_map(text: &str) -> Result<Lut, Error> {
+    let mut colours = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        colours.push(parse_rgb(n + 1, line)?);
+    }
+    resample(&colours)
+}
+
+/// Parses the three components at the start of `line`, ignoring the rest.
+fn parse_rgb(n: usize, line: &str) -> Result<(u8, u8, u8), Error> {
+    let mut fields = line.split_whitespace();
+    let mut component = |name| {
+        let field = fields.next().ok_or_else(|| error(Some(n), format!("missing {} component", name)))?;
+        let value: u32 = field.parse().map_err(|_| error(Some(n), format!("{} component {:?} is not a number", name, field)))?;
+        u8::try_from(value).map_err(|_| error(Some(n), format!("{} component {} is over 255", name, value)))
+    };
+    Ok((component("red")?, component("green")?, component("blue")?))
+}
+
+/// Stretches 2 to 256 colours evenly over the LUT, interpolating linearly in
+/// between, so that short palettes from GIMP make smooth gradients.
+fn resample(colours: &[(u8, u8, u8)]) -> Result<Lut, Error> {
+    if colours.len() < 2 || colours.len() > 256 {
+        return Err(error(None, format!("expected 2 to 256 colours, found {}", colours.len())));
+    }
+    let mut lut = [(0, 0, 0); 256];
+    let last = colours.len() - 1;
+    for (i, entry) in lut.iter_mut().enumerate() {
+        // position along the colours, with 8 fractional bits
+        let position = (i * last * 256) / 255;
+        let (index, frac) = (position >> 8, (position & 0xFF) as i32);
+        let (c0, c1) = (colours[index], colours[(index + 1).min(last)]);
+        let lerp = |x0: u8, x1: u8| (x0 as i32 + (((x1 as i32 - x0 as i32) * frac) >> 8)) as u8;
+        *entry = (lerp(c0.0, c1.0), lerp(c0.1, c1.1), lerp(c0.2, c1.2));
+    }
+    Ok(lut)
+}
@@ -0,0 +1,77 @@
+//! Falling back to cheaper rendering when a frame is running late.
+//!
+//! Each row has to be written before the beam reads it out in the next
+//! refresh, but a render that runs past the top of the next refresh also
+//! delays the one after it, which then starts with little time left for its
+//! first rows. So `Budget` aims to finish by then, extrapolating from how long
+//! the rows so far have taken, and picks a `Quality` for each row to suit.
+
+use crate::escape_time::ITER_MAX;
+use crate::Context;
+
+/// How much work to put into a row.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Quality {
+    Full,
+    /// A quarter fewer iterations, so points that escape late come out as
+    /// interior.
+    FewerIterations,
+    /// Fewer iterations, and only every other checkerboard pixel, written
+    /// twice.
+    Coarse,
+    /// Leave the previous frame's row alone rather than spend time on it.
+    Reuse,
+}
+
+impl Quality {
+    pub fn iter_limit(self) -> i32 {
+        if self == Quality::Full { ITER_MAX } else { ITER_MAX * 3/4 }
+    }
+
+    /// The distance between computed pixels in a checkerboard row.
+    pub fn x_step(self) -> usize {
+        if self == Quality::Coarse { 4 } else { 2 }
+    }
+}
+
+/// Tracks progress through the computed rows `first_y..=last_y` of a frame.
+pub(crate) struct Budget {
+    first_y: usize,
+    last_y: usize,
+    start: i32,
+    frame: u32,
+}
+
+impl Budget {
+    pub fn new(context: &mut dyn Context, first_y: usize, last_y: usize, frame: u32) -> Self {
+        Self { first_y, last_y, start: context.lines_left(0), frame }
+    }
+
+    /// The quality to compute row `pixel_y` at. Time spent waiting for the
+    /// beam counts against the rows too, which only matters when there's
+    /// plenty of time anyway.
+    ///
+    /// Rows whose deadline has passed are reused, as are alternate rows
+    /// (interlaced by frame, so none goes stale) once even `Coarse` is too
+    /// slow.
+    pub fn quality(&self, context: &mut dyn Context, pixel_y: usize) -> Quality {
+        if context.lines_left(pixel_y) < 0 {
+            return Quality::Reuse;
+        }
+        let done = (pixel_y - self.first_y) as i32;
+        if done == 0 {
+            return Quality::Full;
+        }
+        let left = context.lines_left(0);
+        let need = (self.start - left) * (self.last_y + 1 - pixel_y) as i32 / done;
+        if need <= left {
+            Quality::Full
+        } else if need <= 2*left {
+            Quality::FewerIterations
+        } else if need <= 4*left || (pixel_y as u32 + self.frame).is_multiple_of(2) {
+            Quality::Coarse
+        } else {
+            Quality::Reuse
+        }
+    }
+}
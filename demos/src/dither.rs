@@ -0,0 +1,154 @@
+//! Dithering, for pixel formats with fewer levels than the palette.
+//!
+//! Smooth gradients band visibly in RGB565, or in AL44's 16 CLUT entries.
+//! Demos can write their pixels through `Bayer::pixel` or
+//! `Diffusion::pixel` instead of `PixelFormat::from_index` to spread the
+//! rounding error over neighbouring pixels. Neither looks at any row but the
+//! one being written, so they work for pixels written in the order the beam
+//! reads them out, as `wait_for_line` has them, and for L8 they change
+//! nothing.
+
+use crate::{Al44, Colours, PixelFormat, TrueColour, L8};
+
+/// Pixel formats whose channels can be rounded to the nearest of their
+/// levels, with the error left over for dithering.
+pub trait Levels: PixelFormat {
+    /// How far apart the levels of each channel are, roughly.
+    const STEPS: [i32; 3];
+
+    /// The channels of the colour that palette index `index` stands for,
+    /// before rounding.
+    fn channels(index: u8, colours: &Colours) -> [i32; 3];
+
+    /// The pixel whose levels are nearest `channels`, and the channels that
+    /// it shows.
+    fn nearest(channels: [i32; 3]) -> (Self::Pixel, [i32; 3]);
+}
+
+impl Levels for L8 {
+    const STEPS: [i32; 3] = [1, 0, 0];
+
+    #[inline(always)]
+    fn channels(index: u8, _colours: &Colours) -> [i32; 3] {
+        [index as i32, 0, 0]
+    }
+
+    #[inline(always)]
+    fn nearest(channels: [i32; 3]) -> (u8, [i32; 3]) {
+        let index = channels[0].clamp(0, 255);
+        (index as u8, [index, 0, 0])
+    }
+}
+
+/// Dithered in palette indices, which only shows in colour if the palette is
+/// smooth. CLUT entry `e` shows index `16*e + 8`.
+impl Levels for Al44 {
+    const STEPS: [i32; 3] = [16, 0, 0];
+
+    #[inline(always)]
+    fn channels(index: u8, _colours: &Colours) -> [i32; 3] {
+        [index as i32, 0, 0]
+    }
+
+    #[inline(always)]
+    fn nearest(channels: [i32; 3]) -> (u8, [i32; 3]) {
+        let entry = (channels[0] >> 4).clamp(0, 15);
+        (0xF0 | entry as u8, [entry * 16 + 8, 0, 0])
+    }
+}
+
+impl<F: TrueColour> Levels for F {
+    const STEPS: [i32; 3] = [256 >> F::BITS[0], 256 >> F::BITS[1], 256 >> F::BITS[2]];
+
+    #[inline(always)]
+    fn channels(index: u8, colours: &Colours) -> [i32; 3] {
+        let (r, g, b) = colours[index as usize];
+        [r as i32, g as i32, b as i32]
+    }
+
+    #[inline(always)]
+    fn nearest(channels: [i32; 3]) -> (F::Pixel, [i32; 3]) {
+        // the levels are widened by repeating their bits, as `decode` does,
+        // which spaces them 255 / (2^bits - 1) apart
+        let shown: [i32; 3] = core::array::from_fn(|i| {
+            let max = (1 << F::BITS[i]) - 1;
+            let level = (channels[i].clamp(0, 255) * max + 127) / 255;
+            let c = level << (8 - F::BITS[i]);
+            c | c >> F::BITS[i]
+        });
+        (F::encode(shown[0] as u8, shown[1] as u8, shown[2] as u8), shown)
+    }
+}
+
+/// Ordered dithering with a 4x4 or 8x8 Bayer matrix, which adds an offset
+/// that depends only on the pixel's position, so that each tile of the
+/// matrix averages out to the colour wanted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bayer {
+    X4,
+    X8,
+}
+
+impl Bayer {
+    /// The matrix entry for pixel (`pixel_x`, `pixel_y`), from 0 up to one
+    /// less than the number of entries, which interleaves the bits of
+    /// `x ^ y` and `y` from the bottom up.
+    pub fn entry(self, pixel_x: usize, pixel_y: usize) -> u32 {
+        let bits = match self {
+            Bayer::X4 => 2,
+            Bayer::X8 => 3,
+        };
+        let mut entry = 0;
+        for bit in 0..bits {
+            let (x, y) = ((pixel_x >> bit & 1) as u32, (pixel_y >> bit & 1) as u32);
+            entry = entry << 2 | (x ^ y) << 1 | y;
+        }
+        entry
+    }
+
+    /// The pixel for palette index `index` at (`pixel_x`, `pixel_y`).
+    #[inline(always)]
+    pub fn pixel<F: Levels>(self, index: u8, colours: &Colours, pixel_x: usize, pixel_y: usize) -> F::Pixel {
+        let entries = match self {
+            Bayer::X4 => 16,
+            Bayer::X8 => 64,
+        };
+        // the middle of the entry's share of a step, from -1/2 to 1/2 of it
+        let offset = 2 * self.entry(pixel_x, pixel_y) as i32 + 1 - entries;
+        let channels = F::channels(index, colours);
+        F::nearest(core::array::from_fn(|i| channels[i] + offset * F::STEPS[i] / (2 * entries))).0
+    }
+}
+
+/// Error diffusion along each row: the rounding error of a pixel is carried
+/// on to the next one written to its right. Nothing is carried down to the
+/// next row, which may already have been read out, so rows can be written
+/// in any order, and the error starts afresh whenever a pixel isn't to the
+/// right of the last one on the same row.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Diffusion {
+    error: [i32; 3],
+    last: Option<(usize, usize)>,
+}
+
+impl Diffusion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pixel for palette index `index` at (`pixel_x`, `pixel_y`).
+    #[inline(always)]
+    pub fn pixel<F: Levels>(&mut self, index: u8, colours: &Colours, pixel_x: usize, pixel_y: usize) -> F::Pixel {
+        if !matches!(self.last, Some((last_x, last_y)) if last_y == pixel_y && last_x < pixel_x) {
+            self.error = [0; 3];
+        }
+        self.last = Some((pixel_x, pixel_y));
+        let channels = F::channels(index, colours);
+        let wanted: [i32; 3] = core::array::from_fn(|i| channels[i] + self.error[i]);
+        let (pixel, shown) = F::nearest(wanted);
+        // at the ends of the range, where there's no level beyond to make up
+        // for it, the error would otherwise grow without bound
+        self.error = core::array::from_fn(|i| (wanted[i] - shown[i]).clamp(-F::STEPS[i], F::STEPS[i]));
+        pixel
+    }
+}
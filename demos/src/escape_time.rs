@@ -0,0 +1,576 @@
+use crate::fixed::{angle_q16, exp2_q8, log2_q8, sqrt_q8};
+use crate::formula::{Formula, Symmetries};
+use crate::overflow;
+use crate::scheduler::{render_rows, Symmetry};
+use crate::trap::Trap;
+use crate::{Animator, Clut, Colours, Context, Demo, Effect, Fill, Fixed, Framebuffer, Fx32, HsvSweep, Palette, PixelFormat, FRAME_MAX};
+
+pub(crate) const ITER_MAX: i32 = 36;
+
+/// How an escaping point is mapped to a palette index, mostly by making its
+/// escape iteration fractional so that the palette doesn't show a band per
+/// iteration.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Colouring {
+    /// Interpolates linearly between the last two values of |z|^2, which is
+    /// cheap but still shows the bands at low `ITER_MAX`.
+    Lerp,
+    /// The normalized iteration count n + 1 - log_d(log2|z|), for a formula of
+    /// degree d, which is continuous across the bands.
+    Smooth,
+    /// The estimated distance |z| ln|z| / |dz/dz_0| to the set, as the square
+    /// root of a fraction of `DISTANCE_RANGE`, so that filaments thinner than
+    /// a pixel still come out as unbroken lines at index 0.
+    ///
+    /// Only |dz/dz_0| is tracked, as log2 d|z|^(d-1) per iteration, which is
+    /// exact for the Julia sets of z^d + c and close enough elsewhere once the
+    /// orbit has grown.
+    Distance,
+    /// How close the orbit comes to a `Trap` before escaping (or ever, for the
+    /// interior), animated along with the demo.
+    Trap(Trap),
+    /// The iteration band, in the lower or upper half of the palette by
+    /// which of `sectors` equal slices of the turn the angle of z falls in
+    /// when it escapes, alternately. Two sectors give the classic binary
+    /// decomposition, from the sign of Im z alone, and more give external
+    /// field lines, at the cost of an arctangent per escaping point.
+    Decomposition { sectors: u16 },
+}
+
+impl Colouring {
+    /// The colouring to use at frame `t`.
+    pub(crate) fn at(self, t: u32) -> Self {
+        match self {
+            Colouring::Trap(trap) => Colouring::Trap(trap.at(t)),
+            _ => self,
+        }
+    }
+
+    /// The part of `symmetry` that survives colouring this way.
+    ///
+    /// A trap is only symmetric about its own centre, so keeps the rotation
+    /// about the origin only when centred there, and the mirror in the real
+    /// axis only when centred on that. Decomposition sectors are swapped
+    /// round by the rotation, which turns z by half a turn, unless there is a
+    /// multiple of four of them, and by the mirror, which negates the angle of
+    /// z, unless there is an odd number.
+    pub(crate) fn restrict(self, symmetry: Symmetry) -> Symmetry {
+        let (rotate, conjugate) = match self {
+            Colouring::Trap(trap) => (trap.re == Fx32::ZERO && trap.im == Fx32::ZERO, trap.im == Fx32::ZERO),
+            Colouring::Decomposition { sectors } => (sectors.is_multiple_of(4), !sectors.is_multiple_of(2)),
+            _ => (true, true),
+        };
+        match symmetry {
+            Symmetry::FourFold if rotate && conjugate => Symmetry::FourFold,
+            Symmetry::FourFold | Symmetry::Rotate180 if rotate => Symmetry::Rotate180,
+            Symmetry::FourFold | Symmetry::FlipY if conjugate => Symmetry::FlipY,
+            Symmetry::FlipX if rotate && conjugate => Symmetry::FlipX,
+            _ => Symmetry::None,
+        }
+    }
+}
+
+/// How points that don't escape are mapped to a palette index.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interior {
+    /// Index 255, the same as `ITER_MAX`, whatever the `Colouring` (except
+    /// for `Colouring::Trap`, which colours the interior too).
+    Flat,
+    /// Index 255 - 16p for points whose orbit was found to settle into a
+    /// cycle of period p < 16, and as for `Flat` otherwise.
+    Period,
+}
+
+/// log2 of the distance in pixels, in Q8, at and beyond which
+/// `Colouring::Distance` gives the last index before the interior's.
+const DISTANCE_RANGE: i32 = 5 << 8;
+
+/// log2(ln 2) in Q8.
+const LOG2_LN_2: i32 = -135;
+
+/// Iterates `formula` from z = (a, b) until |z| >= 2, and maps the (smoothed
+/// by `colouring`) escape iteration to a palette index. Points that haven't
+/// escaped after `iter_limit` iterations get the same index as after
+/// `ITER_MAX`.
+///
+/// Orbits that come back to exactly where they were stop early as interior
+/// points, as found by Brent's method: z (and z_prev, for formulas that read
+/// it) is compared against a point saved at each power of two iterations, which finds a cycle soon after the orbit
+/// settles into it. Rounding makes an attracting cycle repeat exactly sooner
+/// or later, and unlike allowing some slack, that never mistakes a slow
+/// escape for a cycle. The iterations this saves are counted with
+/// `stats_count_saved_iters`.
+///
+/// `pixel_size` is log2 of the distance between pixels in the plane, in Q8,
+/// for `Colouring::Distance`.
+#[inline(always)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn escape_time<T: Fixed, F: Formula>(formula: &F, context: &mut dyn Context, pixel: (usize, usize), z: (T, T), c: (T, T), iter_limit: i32, colouring: Colouring, interior: Interior, pixel_size: i32) -> u8 {
+    let mut orbit = Orbit::new(z);
+    for iter in 0..iter_limit {
+        let squares = orbit.squares(context);
+        if !orbit.step(formula, context, pixel, c, squares, iter, iter_limit, colouring, interior, pixel_size) {
+            break;
+        }
+    }
+    orbit.index(context, colouring)
+}
+
+/// `escape_time` for `N` pixels at once, iterated in lockstep so that the
+/// Cortex-M7 can issue one orbit's instructions while the other's wait on a
+/// multiply, starting with the squares of every orbit. Orbits that have
+/// finished are masked out until the last one has, and each pixel comes out
+/// exactly as from `escape_time`, overflow reports included: any overflow in
+/// working out an orbit's start (passed in `pending`), setting it up or
+/// squaring it is set aside until that orbit's own step checks it.
+#[inline(always)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn escape_time_lockstep<T: Fixed, F: Formula, const N: usize>(formula: &F, context: &mut dyn Context, pixels: [(usize, usize); N], z: [(T, T); N], mut pending: [overflow::Pending; N], c: [(T, T); N], iter_limit: i32, colouring: Colouring, interior: Interior, pixel_size: i32) -> [u8; N] {
+    let mut orbits: [_; N] = core::array::from_fn(|lane| {
+        overflow::restore(pending[lane]);
+        let orbit = Orbit::new(z[lane]);
+        pending[lane] = overflow::set_aside();
+        orbit
+    });
+    let mut running = [true; N];
+    for iter in 0..iter_limit {
+        let squares: [_; N] = core::array::from_fn(|lane| {
+            if !running[lane] {
+                return (T::ZERO, T::ZERO);
+            }
+            overflow::restore(pending[lane]);
+            let squares = orbits[lane].squares(context);
+            pending[lane] = overflow::set_aside();
+            squares
+        });
+        for lane in 0..N {
+            if running[lane] {
+                overflow::restore(core::mem::take(&mut pending[lane]));
+                running[lane] = orbits[lane].step(formula, context, pixels[lane], c[lane], squares[lane], iter, iter_limit, colouring, interior, pixel_size);
+            }
+        }
+        if running == [false; N] {
+            break;
+        }
+    }
+    core::array::from_fn(|lane| orbits[lane].index(context, colouring))
+}
+
+/// Where an orbit has got to in `escape_time`, and what it has found out on
+/// the way.
+struct Orbit<T> {
+    z: (T, T),
+    // in Q8, whatever the precision of T
+    final_iter: i32,
+    prev_dist: T,
+    prev: (T, T),
+    // log2|dz/dz_0| in Q8, for Colouring::Distance
+    log_dz: i32,
+    // for Colouring::Trap
+    nearest: T,
+    saved: (T, T),
+    saved_prev: (T, T),
+    power: i32,
+    period: i32,
+    /// The palette index, when it was settled before the end.
+    index: Option<u8>,
+}
+
+impl<T: Fixed> Orbit<T> {
+    #[inline(always)]
+    fn new(z: (T, T)) -> Self {
+        Self {
+            z,
+            final_iter: ITER_MAX<<8,
+            prev_dist: T::from_int(-40),
+            prev: (T::ZERO, T::ZERO),
+            log_dz: 0,
+            nearest: T::from_int(64),
+            saved: z,
+            saved_prev: (T::ZERO, T::ZERO),
+            power: 1,
+            period: 0,
+            index: None,
+        }
+    }
+
+    /// The squares of the real and imaginary parts of z, which start each
+    /// iteration.
+    #[inline(always)]
+    fn squares(&self, context: &mut dyn Context) -> (T, T) {
+        let (a, b) = self.z;
+
+        T::count_muls(context, 1);
+        let a2 = a*a;
+
+        T::count_muls(context, 1);
+        let b2 = b*b;
+
+        (a2, b2)
+    }
+
+    /// Runs the rest of iteration `iter`, returning whether the orbit is
+    /// still going.
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn step<F: Formula>(&mut self, formula: &F, context: &mut dyn Context, pixel: (usize, usize), (c_a, c_b): (T, T), (a2, b2): (T, T), iter: i32, iter_limit: i32, colouring: Colouring, interior: Interior, pixel_size: i32) -> bool {
+        let four = T::from_int(4);
+        let (a, b) = self.z;
+
+        context.stats_count_adds(1);
+        let this_dist = a2+b2;
+
+        context.stats_count_cmps(1);
+        if this_dist >= four {
+            match colouring {
+                Colouring::Lerp => {
+                    context.stats_count_adds(2);
+                    context.stats_count_shrs(2);
+                    context.stats_count_cmps(1);
+                    context.stats_count_divs(1);
+                    T::count_cvts(context, 2);
+                    let lerp = ((this_dist - four).to_q(8) << 8) / core::cmp::max((this_dist - self.prev_dist).to_q(8), 1);
+
+                    context.stats_count_adds(1);
+                    context.stats_count_shrs(1);
+                    self.final_iter = (iter << 8) - lerp;
+                }
+                Colouring::Smooth => {
+                    // log2|z| = log2(|z|^2) / 2, which is at least 1 here
+                    // (the max only guards against wrapped overflow)
+                    context.stats_count_cmps(2);
+                    context.stats_count_shrs(5);
+                    context.stats_count_adds(4);
+                    context.stats_count_muls(1);
+                    context.stats_count_mems(2);
+                    T::count_cvts(context, 1);
+                    let log_z = log2_q8(core::cmp::max(this_dist.to_q(8), 4 << 8)) >> 1;
+
+                    context.stats_count_cmps(1);
+                    context.stats_count_shrs(4);
+                    context.stats_count_adds(4);
+                    context.stats_count_muls(1);
+                    context.stats_count_mems(2);
+                    let mut nu = log2_q8(log_z);
+                    if F::DEGREE != 2 {
+                        context.stats_count_shrs(1);
+                        context.stats_count_divs(1);
+                        nu = (nu << 8) / const { log2_q8((F::DEGREE << 8) as i32) };
+                    }
+
+                    // one less than n + 1 - nu, to line up with the lerp
+                    context.stats_count_adds(1);
+                    context.stats_count_shrs(1);
+                    context.stats_count_cmps(1);
+                    self.final_iter = core::cmp::max((iter << 8) - nu, 0);
+                }
+                Colouring::Distance => {
+                    context.stats_count_cmps(2);
+                    context.stats_count_shrs(5);
+                    context.stats_count_adds(4);
+                    context.stats_count_muls(1);
+                    context.stats_count_mems(2);
+                    T::count_cvts(context, 1);
+                    let log_z = log2_q8(core::cmp::max(this_dist.to_q(8), 4 << 8)) >> 1;
+
+                    // log2 of |z| ln|z| / |dz/dz_0|, in pixels
+                    context.stats_count_cmps(1);
+                    context.stats_count_shrs(4);
+                    context.stats_count_adds(8);
+                    context.stats_count_muls(1);
+                    context.stats_count_mems(2);
+                    let log_distance = log_z + log2_q8(log_z) + LOG2_LN_2 - self.log_dz - pixel_size;
+
+                    context.stats_count_cmps(1);
+                    if log_distance >= DISTANCE_RANGE {
+                        self.index = Some(254);
+                        return false;
+                    }
+                    context.stats_count_cmps(14);
+                    context.stats_count_shrs(30);
+                    context.stats_count_adds(30);
+                    context.stats_count_muls(1);
+                    context.stats_count_mems(2);
+                    overflow::check(context, pixel, iter);
+                    self.index = Some(sqrt_q8(exp2_q8(log_distance - DISTANCE_RANGE)) as u8);
+                    return false;
+                }
+                Colouring::Trap(_) => {}
+                Colouring::Decomposition { sectors } => {
+                    let upper = if sectors == 2 {
+                        context.stats_count_cmps(1);
+                        b < T::ZERO
+                    } else {
+                        context.stats_count_shrs(8);
+                        context.stats_count_adds(10);
+                        context.stats_count_cmps(6);
+                        context.stats_count_muls(3);
+                        context.stats_count_divs(1);
+                        T::count_cvts(context, 2);
+                        let angle = angle_q16(a.to_q(12), b.to_q(12));
+                        ((angle * sectors as i32) >> 16) & 1 != 0
+                    };
+                    context.stats_count_muls(1);
+                    context.stats_count_divs(1);
+                    context.stats_count_adds(1);
+                    overflow::check(context, pixel, iter);
+                    self.index = Some((((upper as i32) << 7) | ((iter * 127) / ITER_MAX)) as u8);
+                    return false;
+                }
+            }
+            overflow::check(context, pixel, iter);
+            return false;
+        }
+
+        if colouring == Colouring::Distance {
+            // |f'(z)| = d|z|^(d-1), and log2|z| is half of log2|z|^2, which
+            // stays above 1/2^16 so that it has a logarithm
+            context.stats_count_cmps(3);
+            context.stats_count_shrs(6);
+            context.stats_count_adds(6);
+            context.stats_count_muls(2);
+            context.stats_count_mems(2);
+            T::count_cvts(context, 1);
+            let log_z2 = log2_q8(core::cmp::max(this_dist.to_q(16), 1)) - (8 << 8);
+            self.log_dz += const { log2_q8((F::DEGREE << 8) as i32) } + (((F::DEGREE - 1) as i32 * log_z2) >> 1);
+        }
+
+        if let Colouring::Trap(trap) = colouring {
+            let measure = trap.measure(context, (a, b));
+            context.stats_count_cmps(1);
+            if measure < self.nearest {
+                self.nearest = measure;
+            }
+        }
+
+        self.z = formula.step(context, (a, b), (a2, b2), self.prev, (c_a, c_b));
+        self.prev = (a, b);
+        overflow::check(context, pixel, iter);
+
+        self.period += 1;
+        context.stats_count_adds(1);
+        context.stats_count_cmps(2);
+        if F::READS_PREV {
+            context.stats_count_cmps(2);
+        }
+        if self.z == self.saved && (!F::READS_PREV || self.prev == self.saved_prev) {
+            context.stats_count_saved_iters((iter_limit - iter - 1) as usize);
+            if let (Interior::Period, 1..16) = (interior, self.period) {
+                self.index = Some((255 - 16*self.period) as u8);
+            }
+            return false;
+        }
+        context.stats_count_cmps(1);
+        if self.period == self.power {
+            self.saved = self.z;
+            self.saved_prev = self.prev;
+            self.power <<= 1;
+            self.period = 0;
+        }
+
+        self.prev_dist = this_dist;
+        true
+    }
+
+    /// The palette index once the orbit has stopped.
+    #[inline(always)]
+    fn index(&self, context: &mut dyn Context, colouring: Colouring) -> u8 {
+        if let Some(index) = self.index {
+            return index;
+        }
+        if let Colouring::Trap(trap) = colouring {
+            return trap.index(context, self.nearest);
+        }
+        ((self.final_iter * 255) / (ITER_MAX << 8)) as u8
+    }
+}
+
+/// Which plane an escape-time image is drawn in.
+#[derive(Clone, Copy)]
+pub enum Plane<T> {
+    /// Each pixel is a value of c, and z starts at 0 (like Mandelbrot).
+    Parameter,
+    /// Each pixel is a starting value of z, with c fixed (like Julia).
+    Dynamic { c_a: T, c_b: T },
+}
+
+impl<T: Fixed> Plane<T> {
+    pub fn dynamic(c_a: f32, c_b: f32) -> Self {
+        Plane::Dynamic { c_a: T::from_f32(c_a), c_b: T::from_f32(c_b) }
+    }
+}
+
+/// Extra fractional bits kept in the per-pixel step, so that zoomed-in views
+/// don't round the step to a whole number of units of the fixed-point type.
+pub(crate) const STEP_EXTRA: u32 = 8;
+
+/// The region of the plane shown in a framebuffer.
+pub(crate) struct View<T> {
+    pub re: T,
+    pub im: T,
+    /// Distance between adjacent pixels, with `STEP_EXTRA` extra bits.
+    pub step: T,
+    /// The size of the framebuffer, in pixels.
+    pub fb_w: usize,
+    pub fb_h: usize,
+}
+
+impl<T: Fixed> View<T> {
+    /// A view centred on `re + im*i`, `height` units tall, shown in `fb`.
+    pub fn new<F: PixelFormat>(re: T, im: T, height: T, fb: &Framebuffer<F>) -> Self {
+        Self { re, im, step: (height << STEP_EXTRA).div_int(fb.height() as i32), fb_w: fb.width(), fb_h: fb.height() }
+    }
+
+    /// log2 of the distance between pixels, in Q8, as `escape_time` takes.
+    pub fn pixel_size(&self) -> i32 {
+        // finer for deep zooms, as far as an i32 has room for; the step then
+        // has bits - 8 + STEP_EXTRA more bits than the Q8 the log works in
+        let bits = T::FRAC_BITS.clamp(16, 24);
+        log2_q8(core::cmp::max(self.step.to_q(bits), 1)) - (((bits - 8 + STEP_EXTRA) as i32) << 8)
+    }
+
+    #[inline(always)]
+    pub fn point(&self, pixel_x: usize, pixel_y: usize) -> (T, T) {
+        (self.re + (self.step.mul_int(2*pixel_x as i32 - (self.fb_w as i32 - 1)) >> (STEP_EXTRA + 1)),
+         self.im + (self.step.mul_int(2*pixel_y as i32 - (self.fb_h as i32 - 1)) >> (STEP_EXTRA + 1)))
+    }
+}
+
+impl Symmetry {
+    /// The symmetry that a formula with `symmetries` gives `view` of `plane`.
+    pub fn of<T: Fixed>(symmetries: Symmetries, plane: Plane<T>, view: &View<T>) -> Self {
+        let (rotate, conjugate) = match plane {
+            Plane::Parameter => (symmetries.odd, symmetries.conjugate),
+            Plane::Dynamic { c_b, .. } => (symmetries.even, symmetries.conjugate && c_b == T::ZERO),
+        };
+        let rotate = rotate && view.re == T::ZERO && view.im == T::ZERO;
+        let conjugate = conjugate && view.im == T::ZERO;
+        match (rotate, conjugate) {
+            (true, true) => Symmetry::FourFold,
+            (true, false) => Symmetry::Rotate180,
+            (false, true) => Symmetry::FlipY,
+            (false, false) => Symmetry::None,
+        }
+    }
+}
+
+/// How an escape-time demo colours its pixels and fills them in, which every
+/// demo here sets through the builders of `Styled`.
+#[derive(Clone, Copy)]
+pub struct Style {
+    pub(crate) colouring: Colouring,
+    pub(crate) interior: Interior,
+    pub(crate) fill: Fill,
+    lut: Animator,
+}
+
+impl Style {
+    pub(crate) fn new() -> Self {
+        Self { colouring: Colouring::Lerp, interior: Interior::Flat, fill: Fill::Checkerboard, lut: Animator::new(&HsvSweep, &[]) }
+    }
+
+    pub(crate) fn pre_render<P: PixelFormat>(&mut self, context: &mut dyn Context, clut: &mut Clut) {
+        self.lut.write::<P>(context, clut);
+    }
+
+    /// The colours of the frame about to be rendered, worked out at the start
+    /// of `render` to leave only the CLUT writes for the vertical blanking
+    /// period.
+    pub(crate) fn prepare<'a>(&mut self, clut: &'a mut Clut) -> &'a Colours {
+        self.lut.prepare(clut)
+    }
+
+    /// Renders frame `frame` of `view` of `formula` in `plane` into `fb`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn render_view<F: Formula, T: Fixed, P: PixelFormat>(&self, context: &mut dyn Context, fb: &mut Framebuffer<P>, colours: &Colours, frame: u32, formula: &F, plane: Plane<T>, view: &View<T>) {
+        let symmetry = Symmetry::of(F::SYMMETRIES, plane, view);
+        let pixel_size = view.pixel_size();
+        let colouring = self.colouring.at(frame);
+        render_rows(context, fb, frame, colouring.restrict(symmetry), self.fill, |context: &mut dyn Context, pixel_x, pixel_y, iter_limit| {
+            let p = view.point(pixel_x, pixel_y);
+            let index = match plane {
+                // z starts at 0, so the first iteration always lands on c
+                Plane::Parameter => escape_time(formula, context, (pixel_x, pixel_y), p, p, iter_limit, colouring, self.interior, pixel_size),
+                Plane::Dynamic { c_a, c_b } => escape_time(formula, context, (pixel_x, pixel_y), p, (c_a, c_b), iter_limit, colouring, self.interior, pixel_size),
+            };
+            P::from_index(index, colours)
+        });
+    }
+}
+
+/// The builders shared by every escape-time demo, which set its `Style`.
+pub trait Styled: Sized {
+    fn style_mut(&mut self) -> &mut Style;
+
+    fn with_colouring(mut self, colouring: Colouring) -> Self {
+        self.style_mut().colouring = colouring;
+        self
+    }
+
+    fn with_interior(mut self, interior: Interior) -> Self {
+        self.style_mut().interior = interior;
+        self
+    }
+
+    fn with_fill(mut self, fill: Fill) -> Self {
+        self.style_mut().fill = fill;
+        self
+    }
+
+    fn with_palette(mut self, palette: &'static dyn Palette) -> Self {
+        let style = self.style_mut();
+        style.lut = style.lut.with_palette(palette);
+        self
+    }
+
+    fn with_effects(mut self, effects: &'static [Effect]) -> Self {
+        let style = self.style_mut();
+        style.lut = style.lut.with_effects(effects);
+        self
+    }
+}
+
+/// A still view of an escape-time fractal, with an animated palette, computed
+/// with fixed-point numbers of type `T`.
+pub struct EscapeTime<F: Formula, T: Fixed = Fx32<10>> {
+    formula: F,
+    plane: Plane<T>,
+    /// The centre of the view, and its height.
+    re: T,
+    im: T,
+    height: T,
+    style: Style,
+    frame: u32,
+}
+
+impl<F: Formula, T: Fixed> EscapeTime<F, T> {
+    /// Shows `formula` in `plane`, centred on `re + im*i` and `height` units
+    /// tall.
+    pub fn new(formula: F, plane: Plane<T>, re: f32, im: f32, height: f32) -> Self {
+        let (re, im, height) = (T::from_f32(re), T::from_f32(im), T::from_f32(height));
+        Self { formula, plane, re, im, height, style: Style::new(), frame: 0 }
+    }
+}
+
+impl<F: Formula, T: Fixed> Styled for EscapeTime<F, T> {
+    fn style_mut(&mut self) -> &mut Style {
+        &mut self.style
+    }
+}
+
+impl<F: Formula, T: Fixed, P: PixelFormat> Demo<P> for EscapeTime<F, T> {
+    fn pre_render(&mut self, context: &mut dyn Context, clut: &mut Clut) {
+        self.style.pre_render::<P>(context, clut);
+    }
+
+    fn render(&mut self, context: &mut dyn Context, clut: &mut Clut, fb: &mut Framebuffer<P>) {
+        self.frame += 1;
+        if self.frame >= FRAME_MAX {
+            self.frame = 0;
+        }
+        self.formula.animate(self.frame);
+        let colours = self.style.prepare(clut);
+        let view = View::new(self.re, self.im, self.height, fb);
+        self.style.render_view(context, fb, colours, self.frame, &self.formula, self.plane, &view);
+    }
+}
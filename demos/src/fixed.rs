@@ -0,0 +1,275 @@
+use core::ops::{Add, Div, Mul, Neg, Shl, Shr, Sub};
+
+use crate::overflow::{arith, Op};
+use crate::Context;
+
+/// A signed fixed-point number, as used by the kernels in place of floats.
+///
+/// Implemented by `Fx32<F>` and `Fx64<F>`, which have `F` fractional bits.
+/// `Fx32` forms products in the backing type before shifting them back down,
+/// which takes a single MUL but only suits F up to about 12 for values of
+/// magnitude below 8. `Fx64` forms them in full from four 32 by 32 bit long
+/// multiplies (UMULL, SMULL and friends), which suits F up to about 40, for
+/// deep zooms. `F32` implements it with the FPU instead, for comparison.
+///
+/// Arithmetic wraps on overflow, unless the `checked` feature is enabled (see
+/// the `overflow` module).
+pub trait Fixed: Copy + PartialEq + PartialOrd
+    + Add<Output = Self> + Sub<Output = Self> + Neg<Output = Self>
+    + Mul<Output = Self> + Div<Output = Self>
+    + Shl<u32, Output = Self> + Shr<u32, Output = Self>
+{
+    const FRAC_BITS: u32;
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn from_int(x: i32) -> Self;
+    fn from_f32(x: f32) -> Self;
+    /// Converts from a raw value with `bits` fractional bits.
+    fn from_q(x: i32, bits: u32) -> Self;
+    /// Converts to a raw value with `bits` fractional bits, truncating.
+    fn to_q(self, bits: u32) -> i32;
+    fn mul_int(self, n: i32) -> Self;
+    fn div_int(self, n: i32) -> Self;
+    fn abs(self) -> Self;
+
+    /// Counts `n` uses of `*` in the stats, along with the shifts back down
+    /// that fixed-point products need.
+    fn count_muls(context: &mut dyn Context, n: usize);
+    /// Counts `n` conversions to or from integers in the stats, which are
+    /// free for fixed-point numbers beyond the shifts already counted.
+    #[inline(always)]
+    fn count_cvts(_context: &mut dyn Context, _n: usize) {}
+}
+
+macro_rules! fixed {
+    ($name:ident, $backing:ty, $wide:ty, $mul:ident, $muls:expr) => {
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+        pub struct $name<const F: u32>(pub $backing);
+
+        impl<const F: u32> Add for $name<F> {
+            type Output = Self;
+            #[inline(always)]
+            fn add(self, rhs: Self) -> Self {
+                Self(arith!(Op::Add, self.0.checked_add(rhs.0), self.0.saturating_add(rhs.0), self.0.wrapping_add(rhs.0)))
+            }
+        }
+
+        impl<const F: u32> Sub for $name<F> {
+            type Output = Self;
+            #[inline(always)]
+            fn sub(self, rhs: Self) -> Self {
+                Self(arith!(Op::Sub, self.0.checked_sub(rhs.0), self.0.saturating_sub(rhs.0), self.0.wrapping_sub(rhs.0)))
+            }
+        }
+
+        impl<const F: u32> Neg for $name<F> {
+            type Output = Self;
+            #[inline(always)]
+            fn neg(self) -> Self {
+                Self(arith!(Op::Neg, self.0.checked_neg(), self.0.saturating_neg(), self.0.wrapping_neg()))
+            }
+        }
+
+        impl<const F: u32> Mul for $name<F> {
+            type Output = Self;
+            #[inline(always)]
+            #[allow(clippy::suspicious_arithmetic_impl)]
+            fn mul(self, rhs: Self) -> Self {
+                Self($mul(self.0, rhs.0, F))
+            }
+        }
+
+        /// Divides in double width, which is slow on the Cortex-M7 (there is
+        /// no 64-bit SDIV), so keep this out of the kernels.
+        impl<const F: u32> Div for $name<F> {
+            type Output = Self;
+            fn div(self, rhs: Self) -> Self { Self((((self.0 as $wide) << F) / rhs.0 as $wide) as $backing) }
+        }
+
+        impl<const F: u32> Shl<u32> for $name<F> {
+            type Output = Self;
+            #[inline(always)]
+            fn shl(self, n: u32) -> Self {
+                let shifted = self.0 << n;
+                Self(arith!(Op::Shl,
+                            if shifted >> n == self.0 { Some(shifted) } else { None },
+                            if self.0 < 0 { <$backing>::MIN } else { <$backing>::MAX },
+                            shifted))
+            }
+        }
+
+        impl<const F: u32> Shr<u32> for $name<F> {
+            type Output = Self;
+            #[inline(always)]
+            fn shr(self, n: u32) -> Self { Self(self.0 >> n) }
+        }
+
+        impl<const F: u32> Fixed for $name<F> {
+            const FRAC_BITS: u32 = F;
+            const ZERO: Self = Self(0);
+            const ONE: Self = Self(1 << F);
+
+            #[inline(always)]
+            fn from_int(x: i32) -> Self { Self(x as $backing) << F }
+            #[inline(always)]
+            fn from_f32(x: f32) -> Self { Self((x * ((1 as $backing) << F) as f32) as $backing) }
+            #[inline(always)]
+            fn from_q(x: i32, bits: u32) -> Self {
+                if bits > F { Self((x >> (bits - F)) as $backing) } else { Self((x as $backing) << (F - bits)) }
+            }
+            #[inline(always)]
+            fn to_q(self, bits: u32) -> i32 {
+                (if bits > F { self.0 << (bits - F) } else { self.0 >> (F - bits) }) as i32
+            }
+            #[inline(always)]
+            fn mul_int(self, n: i32) -> Self {
+                let n = n as $backing;
+                Self(arith!(Op::Mul, self.0.checked_mul(n), self.0.saturating_mul(n), self.0.wrapping_mul(n)))
+            }
+            #[inline(always)]
+            fn div_int(self, n: i32) -> Self { Self(self.0 / n as $backing) }
+            #[inline(always)]
+            fn abs(self) -> Self { Self(self.0.abs()) }
+
+            #[inline(always)]
+            fn count_muls(context: &mut dyn Context, n: usize) {
+                context.stats_count_muls($muls * n);
+                context.stats_count_shrs(n);
+            }
+        }
+    };
+}
+
+/// The product of `a` and `b`, shifted down `shift` places, formed in the
+/// backing type and so only good while that doesn't overflow.
+#[inline(always)]
+fn mul_i32(a: i32, b: i32, shift: u32) -> i32 {
+    arith!(Op::Mul, a.checked_mul(b), a.saturating_mul(b), a.wrapping_mul(b)) >> shift
+}
+
+/// The product of `a` and `b`, shifted down `shift` places, formed in full.
+///
+/// The 128-bit product is put together from the four products of the 32-bit
+/// halves, each of which fits in 64 bits, so only the sums and the shift are
+/// done in 128 bits and there's no general 128-bit multiply. It is still
+/// several times the work of an `Fx32` product.
+#[inline(always)]
+fn mul_i64(a: i64, b: i64, shift: u32) -> i64 {
+    let (a_hi, a_lo) = ((a >> 32) as i32, a as u32);
+    let (b_hi, b_lo) = ((b >> 32) as i32, b as u32);
+    let lo = a_lo as u64 * b_lo as u64;
+    let mid = (a_hi as i64 * b_lo as i64) as i128 + (a_lo as i64 * b_hi as i64) as i128;
+    let hi = a_hi as i64 * b_hi as i64;
+    let product = ((hi as i128) << 64) + (mid << 32) + lo as i128;
+    let shifted = product >> shift;
+    arith!(Op::Mul, i64::try_from(shifted).ok(), if shifted < 0 { i64::MIN } else { i64::MAX }, shifted as i64)
+}
+
+fixed!(Fx32, i32, i64, mul_i32, 1);
+fixed!(Fx64, i64, i128, mul_i64, 4);
+
+/// The fractional bits of the angle used inside `cos_sin`. The cubic is only
+/// good to about 1% anyway, so there's nothing to gain from more.
+const TRIG_BITS: u32 = 14;
+
+/// sin(x * pi/2) for x in [0, 1], with x and the result in `TRIG_BITS` format.
+fn sin_internal(offset: i32) -> i32 {
+    assert!((0..=(1<<TRIG_BITS)).contains(&offset));
+    let x = offset as i64;
+    ((x * ((3 << (2*TRIG_BITS)) - x*x)) >> (2*TRIG_BITS + 1)) as i32
+}
+
+/// The cosine and sine of `theta` quarter turns, where theta lies in [0, 4].
+pub fn cos_sin<T: Fixed>(theta: T) -> (T, T) {
+    const ONE: i32 = 1 << TRIG_BITS;
+    let theta = theta.to_q(TRIG_BITS);
+    assert!((0..=4*ONE).contains(&theta));
+    let (cos, sin) = if theta <= ONE {
+        (sin_internal(ONE - theta), sin_internal(theta))
+    } else if theta <= 2*ONE {
+        (-sin_internal(theta - ONE), sin_internal(2*ONE - theta))
+    } else if theta <= 3*ONE {
+        (-sin_internal(3*ONE - theta), -sin_internal(theta - 2*ONE))
+    } else {
+        (sin_internal(theta - 3*ONE), -sin_internal(4*ONE - theta))
+    };
+    (T::from_q(cos, TRIG_BITS), T::from_q(sin, TRIG_BITS))
+}
+
+/// log2(1 + i/16) in Q8, for i in 0..=16.
+const LOG2_TABLE: [u16; 17] = [0, 22, 44, 63, 82, 100, 118, 134, 150, 165, 179, 193, 207, 220, 232, 244, 256];
+
+/// log2 of `x`, with x (which must be positive) and the result in Q8.
+///
+/// The mantissa is looked up in `LOG2_TABLE` and interpolated linearly, which
+/// is good to about 1/256 and costs a CLZ, a few shifts and two loads.
+pub(crate) const fn log2_q8(x: i32) -> i32 {
+    let msb = 31 - x.leading_zeros() as i32;
+    // x scaled to [1, 2) in Q8
+    let mantissa = if msb >= 8 { x >> (msb - 8) } else { x << (8 - msb) };
+    let i = ((mantissa - 256) >> 4) as usize;
+    let frac = (mantissa - 256) & 15;
+    let (lo, hi) = (LOG2_TABLE[i] as i32, LOG2_TABLE[i + 1] as i32);
+    ((msb - 8) << 8) + lo + (((hi - lo) * frac) >> 4)
+}
+
+/// 2^(i/16) in Q8, for i in 0..=16.
+const EXP2_TABLE: [u16; 17] = [256, 267, 279, 292, 304, 318, 332, 347, 362, 378, 395, 412, 431, 450, 470, 490, 512];
+
+/// 2 to the power of `x`, with x and the result in Q8, the inverse of
+/// `log2_q8`. Results below 1/256 come out as 0.
+pub(crate) const fn exp2_q8(x: i32) -> i32 {
+    let int = x >> 8;
+    let i = ((x & 255) >> 4) as usize;
+    let frac = x & 15;
+    let (lo, hi) = (EXP2_TABLE[i] as i32, EXP2_TABLE[i + 1] as i32);
+    // in [1, 2), in Q8
+    let mantissa = lo + (((hi - lo) * frac) >> 4);
+    if int >= 0 { mantissa << int } else if int > -9 { mantissa >> -int } else { 0 }
+}
+
+/// The square root of `x`, with x (which must be non-negative and below 2^15)
+/// and the result in Q8.
+///
+/// Works out one bit of the result per step, so it costs a dozen compares and
+/// subtracts rather than a division.
+pub(crate) const fn sqrt_q8(x: i32) -> i32 {
+    // sqrt(x / 2^8) * 2^8 = sqrt(x * 2^8)
+    let mut rem = (x as u32) << 8;
+    let mut root = 0;
+    let mut bit = 1 << 22;
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root as i32
+}
+
+/// The angle of (x, y) anticlockwise from the positive x axis, in turns in
+/// Q16 (so in 0..65536), for x and y of any one scale below 2^19.
+///
+/// Within each octant, atan(t) is approximated by pi/4 t + 0.273 t (1 - t),
+/// which is good to about 1/1500 of a turn and costs a division.
+pub(crate) fn angle_q16(x: i32, y: i32) -> i32 {
+    let (abs_x, abs_y) = (x.abs(), y.abs());
+    let (num, den) = if abs_y <= abs_x { (abs_y, abs_x) } else { (abs_x, abs_y) };
+    if den == 0 {
+        return 0;
+    }
+    // t in Q12, and its arctangent in turns in Q16: 1/8 t + 0.0435 t (1 - t)
+    let t = (num << 12) / den;
+    let octant = (t << 1) + ((((t * ((1 << 12) - t)) >> 12) * 2848) >> 12);
+    let quadrant = if abs_y <= abs_x { octant } else { (1 << 14) - octant };
+    match (x >= 0, y >= 0) {
+        (true, true) => quadrant,
+        (false, true) => (1 << 15) - quadrant,
+        (false, false) => (1 << 15) + quadrant,
+        (true, false) => ((1 << 16) - quadrant) & 0xFFFF,
+    }
+}
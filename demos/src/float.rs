@@ -0,0 +1,97 @@
+//! An `f32` stand-in for the fixed-point types, to compare the kernels on the
+//! Cortex-M7's single-precision FPU against the integer ones.
+
+use core::ops::{Add, Div, Mul, Neg, Shl, Shr, Sub};
+
+use crate::{Context, Fixed};
+
+/// An `f32` that the kernels can take in place of a fixed-point number.
+///
+/// Its multiplies are counted with `stats_count_fmuls` and its conversions to
+/// and from integers with `stats_count_fcvts`. Shifts scale by a power of two,
+/// which the compiler folds into a multiply. Floats don't wrap, so there's
+/// nothing for the `checked` feature to report.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Default, Debug)]
+pub struct F32(pub f32);
+
+/// 2 to the power of `n`.
+#[inline(always)]
+fn scale(n: u32) -> f32 {
+    (1u64 << n) as f32
+}
+
+impl Add for F32 {
+    type Output = Self;
+    #[inline(always)]
+    fn add(self, rhs: Self) -> Self { Self(self.0 + rhs.0) }
+}
+
+impl Sub for F32 {
+    type Output = Self;
+    #[inline(always)]
+    fn sub(self, rhs: Self) -> Self { Self(self.0 - rhs.0) }
+}
+
+impl Neg for F32 {
+    type Output = Self;
+    #[inline(always)]
+    fn neg(self) -> Self { Self(-self.0) }
+}
+
+impl Mul for F32 {
+    type Output = Self;
+    #[inline(always)]
+    fn mul(self, rhs: Self) -> Self { Self(self.0 * rhs.0) }
+}
+
+/// VDIV takes 14 cycles, so keep this out of the kernels as for fixed point.
+impl Div for F32 {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self { Self(self.0 / rhs.0) }
+}
+
+impl Shl<u32> for F32 {
+    type Output = Self;
+    #[inline(always)]
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn shl(self, n: u32) -> Self { Self(self.0 * scale(n)) }
+}
+
+impl Shr<u32> for F32 {
+    type Output = Self;
+    #[inline(always)]
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn shr(self, n: u32) -> Self { Self(self.0 / scale(n)) }
+}
+
+impl Fixed for F32 {
+    /// The bits of the mantissa, which are fractional bits for values in
+    /// [1, 2) and finer below.
+    const FRAC_BITS: u32 = 23;
+    const ZERO: Self = Self(0.0);
+    const ONE: Self = Self(1.0);
+
+    #[inline(always)]
+    fn from_int(x: i32) -> Self { Self(x as f32) }
+    #[inline(always)]
+    fn from_f32(x: f32) -> Self { Self(x) }
+    #[inline(always)]
+    fn from_q(x: i32, bits: u32) -> Self { Self(x as f32 / scale(bits)) }
+    #[inline(always)]
+    fn to_q(self, bits: u32) -> i32 { (self.0 * scale(bits)) as i32 }
+    #[inline(always)]
+    fn mul_int(self, n: i32) -> Self { Self(self.0 * n as f32) }
+    #[inline(always)]
+    fn div_int(self, n: i32) -> Self { Self(self.0 / n as f32) }
+    #[inline(always)]
+    fn abs(self) -> Self { Self(self.0.abs()) }
+
+    #[inline(always)]
+    fn count_muls(context: &mut dyn Context, n: usize) {
+        context.stats_count_fmuls(n);
+    }
+    #[inline(always)]
+    fn count_cvts(context: &mut dyn Context, n: usize) {
+        context.stats_count_fcvts(n);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{cos_sin, Context, Fixed, Fx32, FRAME_MAX};
+
+/// Algebraic properties of an iteration formula f(z, z_prev, c), from which
+/// `Symmetry::of` works out which image symmetries a particular view has.
+#[derive(Clone, Copy)]
+pub struct Symmetries {
+    /// f(conj z, conj z_prev, conj c) = conj f(z, z_prev, c), so the image is
+    /// mirrored about the real axis when c (or the view of c) is real.
+    pub conjugate: bool,
+    /// f(-z, -z_prev, c) = f(z, z_prev, c), so every dynamic plane image is
+    /// symmetric under 180 degree rotation about the origin.
+    pub even: bool,
+    /// f(-z, -z_prev, -c) = -f(z, z_prev, c), so the parameter plane image is
+    /// symmetric under 180 degree rotation about the origin.
+    pub odd: bool,
+}
+
+/// One step of an escape-time iteration.
+pub trait Formula {
+    const SYMMETRIES: Symmetries;
+    /// The power of z that dominates once an orbit escapes, which sets how
+    /// fast it grows, as smooth colouring needs to know.
+    const DEGREE: u32 = 2;
+    /// Whether `step` reads `prev`, which makes z and z_prev together the
+    /// state of an orbit, as periodicity checking then has to compare.
+    const READS_PREV: bool = false;
+
+    /// Returns z_{n+1}, given z_n = `z`, its component squares `z2` (already
+    /// computed for the bailout test), z_{n-1} = `prev` and `c`.
+    fn step<T: Fixed>(&self, context: &mut dyn Context, z: (T, T), z2: (T, T), prev: (T, T), c: (T, T)) -> (T, T);
+
+    /// Called once per frame, for formulas with animated parameters.
+    fn animate(&mut self, _frame: u32) {}
+}
+
+/// z^2 + c, as used by the Julia and Mandelbrot sets.
+pub struct Quadratic;
+
+impl Formula for Quadratic {
+    const SYMMETRIES: Symmetries = Symmetries { conjugate: true, even: true, odd: false };
+
+    #[inline(always)]
+    fn step<T: Fixed>(&self, context: &mut dyn Context, (a, b): (T, T), (a2, b2): (T, T), _prev: (T, T), (c_a, c_b): (T, T)) -> (T, T) {
+        T::count_muls(context, 1);
+        context.stats_count_shrs(1);
+        let two_ab = (a << 1) * b;
+
+        context.stats_count_adds(2);
+        let a = a2 - b2 + c_a;
+
+        context.stats_count_adds(1);
+        let b = two_ab + c_b;
+
+        (a, b)
+    }
+}
+
+/// (|Re z| + i|Im z|)^2 + c
+pub struct BurningShip;
+
+impl Formula for BurningShip {
+    const SYMMETRIES: Symmetries = Symmetries { conjugate: false, even: true, odd: false };
+
+    #[inline(always)]
+    fn step<T: Fixed>(&self, context: &mut dyn Context, (a, b): (T, T), (a2, b2): (T, T), _prev: (T, T), (c_a, c_b): (T, T)) -> (T, T) {
+        T::count_muls(context, 1);
+        context.stats_count_shrs(1);
+        context.stats_count_cmps(1);
+        let two_ab = ((a << 1) * b).abs();
+
+        context.stats_count_adds(2);
+        let a = a2 - b2 + c_a;
+
+        context.stats_count_adds(1);
+        let b = two_ab + c_b;
+
+        (a, b)
+    }
+}
+
+/// conj(z)^2 + c, also known as the Mandelbar set.
+pub struct Tricorn;
+
+impl Formula for Tricorn {
+    const SYMMETRIES: Symmetries = Symmetries { conjugate: true, even: true, odd: false };
+
+    #[inline(always)]
+    fn step<T: Fixed>(&self, context: &mut dyn Context, (a, b): (T, T), (a2, b2): (T, T), _prev: (T, T), (c_a, c_b): (T, T)) -> (T, T) {
+        T::count_muls(context, 1);
+        context.stats_count_shrs(1);
+        let two_ab = (a << 1) * b;
+
+        context.stats_count_adds(2);
+        let a = a2 - b2 + c_a;
+
+        context.stats_count_adds(1);
+        let b = c_b - two_ab;
+
+        (a, b)
+    }
+}
+
+/// z^N + c. Since |z| < 2 going in, N up to 5 stays within `Fx32<10>`.
+pub struct Multibrot<const N: u32>;
+
+impl<const N: u32> Formula for Multibrot<N> {
+    const SYMMETRIES: Symmetries = Symmetries { conjugate: true, even: N.is_multiple_of(2), odd: !N.is_multiple_of(2) };
+    const DEGREE: u32 = N;
+
+    #[inline(always)]
+    fn step<T: Fixed>(&self, context: &mut dyn Context, (a, b): (T, T), (a2, b2): (T, T), _prev: (T, T), (c_a, c_b): (T, T)) -> (T, T) {
+        const { assert!(N >= 2 && N <= 5) };
+
+        T::count_muls(context, 1);
+        context.stats_count_shrs(1);
+        context.stats_count_adds(1);
+        let (mut p_a, mut p_b) = (a2 - b2, (a << 1) * b);
+
+        for _ in 2..N {
+            T::count_muls(context, 4);
+            context.stats_count_adds(2);
+            (p_a, p_b) = (p_a*a - p_b*b, p_a*b + p_b*a);
+        }
+
+        context.stats_count_adds(2);
+        (p_a + c_a, p_b + c_b)
+    }
+}
+
+/// z^2 + c + p*z_prev, with real c and p. The p term breaks the 180 degree
+/// symmetry of the quadratic Julia sets, so only the mirror survives.
+pub struct Phoenix {
+    p: Fx32<16>,
+}
+
+impl Phoenix {
+    pub fn new() -> Self {
+        Self { p: Fx32::from_f32(-0.5) }
+    }
+}
+
+impl Default for Phoenix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Formula for Phoenix {
+    const SYMMETRIES: Symmetries = Symmetries { conjugate: true, even: false, odd: false };
+    const READS_PREV: bool = true;
+
+    #[inline(always)]
+    fn step<T: Fixed>(&self, context: &mut dyn Context, (a, b): (T, T), (a2, b2): (T, T), (prev_a, prev_b): (T, T), (c_a, c_b): (T, T)) -> (T, T) {
+        T::count_muls(context, 1);
+        context.stats_count_shrs(1);
+        let two_ab = (a << 1) * b;
+
+        T::count_muls(context, 2);
+        let p = T::from_q(self.p.0, 16);
+        let (p_a, p_b) = (p*prev_a, p*prev_b);
+
+        context.stats_count_adds(3);
+        let a = a2 - b2 + c_a + p_a;
+
+        context.stats_count_adds(2);
+        let b = two_ab + c_b + p_b;
+
+        (a, b)
+    }
+
+    fn animate(&mut self, frame: u32) {
+        // p wobbles around -0.5 by +/- 1/16
+        let (_, sin) = cos_sin(Fx32::<16>::from_int(4 * frame as i32).div_int(FRAME_MAX as i32));
+        self.p = Fx32::from_f32(-0.5) + (sin >> 4);
+    }
+}
@@ -0,0 +1,119 @@
+//! The framebuffer that demos render into.
+//!
+//! The host owns the pixels (the firmware takes the `.fb` section with
+//! `take_framebuffer!`, the emulator allocates them) and lends them to
+//! `Demo::render` as a `Framebuffer`, which knows the dimensions and stride and
+//! hands out rows. Rendering races the beam, so parts of the frame are written
+//! while others are copied from, which `split_at_row_mut` allows without any
+//! aliasing.
+
+use core::ops::Range;
+
+use crate::{PixelFormat, L8};
+
+/// A `width` by `height` image in pixel format `F`, with rows `stride` pixels
+/// apart.
+pub struct Framebuffer<'a, F: PixelFormat = L8> {
+    pixels: &'a mut [F::Pixel],
+    width: usize,
+    height: usize,
+    stride: usize,
+}
+
+impl<'a, F: PixelFormat> Framebuffer<'a, F> {
+    /// Borrows `pixels` as a framebuffer, which must be long enough to hold
+    /// `height` rows `stride` pixels apart.
+    pub fn new(pixels: &'a mut [F::Pixel], width: usize, height: usize, stride: usize) -> Self {
+        assert!(width <= stride);
+        assert!(height == 0 || pixels.len() >= (height - 1) * stride + width);
+        Self { pixels, width, height, stride }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The distance between the starts of consecutive rows, in pixels.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// The distance between the starts of consecutive rows, in bytes.
+    pub fn stride_bytes(&self) -> usize {
+        self.stride * core::mem::size_of::<F::Pixel>()
+    }
+
+    /// The address of the first pixel, for the LTDC to read out from.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.pixels.as_ptr() as *const u8
+    }
+
+    pub fn row(&self, pixel_y: usize) -> &[F::Pixel] {
+        assert!(pixel_y < self.height);
+        &self.pixels[pixel_y * self.stride..][..self.width]
+    }
+
+    pub fn row_mut(&mut self, pixel_y: usize) -> &mut [F::Pixel] {
+        assert!(pixel_y < self.height);
+        &mut self.pixels[pixel_y * self.stride..][..self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[F::Pixel]> {
+        (0..self.height).map(|pixel_y| self.row(pixel_y))
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [F::Pixel]> {
+        let width = self.width;
+        self.pixels.chunks_mut(self.stride).take(self.height).map(move |row| &mut row[..width])
+    }
+
+    /// Reborrows rows `lines`, as a framebuffer of their own whose row 0 is
+    /// row `lines.start` of this one.
+    pub fn lines_mut(&mut self, lines: Range<usize>) -> Framebuffer<'_, F> {
+        assert!(lines.start <= lines.end && lines.end <= self.height);
+        let start = core::cmp::min(lines.start * self.stride, self.pixels.len());
+        Framebuffer { pixels: &mut self.pixels[start..], width: self.width, height: lines.len(), stride: self.stride }
+    }
+
+    /// Splits into rows `..pixel_y` and rows `pixel_y..`, which can be
+    /// borrowed at the same time.
+    pub fn split_at_row_mut(&mut self, pixel_y: usize) -> (Framebuffer<'_, F>, Framebuffer<'_, F>) {
+        assert!(pixel_y <= self.height);
+        let mid = core::cmp::min(pixel_y * self.stride, self.pixels.len());
+        let (top, bottom) = self.pixels.split_at_mut(mid);
+        let (width, stride) = (self.width, self.stride);
+        (Framebuffer { pixels: top, width, height: pixel_y, stride },
+         Framebuffer { pixels: bottom, width, height: self.height - pixel_y, stride })
+    }
+
+    /// Sets every pixel to `value`, leaving any padding between rows alone.
+    pub fn fill(&mut self, value: F::Pixel) {
+        for row in self.rows_mut() {
+            row.fill(value);
+        }
+    }
+}
+
+/// Declares a `width` by `height` framebuffer in pixel format `format` in the
+/// `.fb` section, which evaluates to it the first time it runs and to `None`
+/// after that.
+#[cfg(feature = "real")]
+#[macro_export]
+macro_rules! take_framebuffer {
+    ($format:ty, $width:expr, $height:expr) => {{
+        #[link_section = ".fb"]
+        static mut FB: [<$format as $crate::PixelFormat>::Pixel; $width * $height] = [0; $width * $height];
+        static TAKEN: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+        if TAKEN.swap(true, core::sync::atomic::Ordering::AcqRel) {
+            None
+        } else {
+            // only ever borrowed once, just above
+            let pixels: &'static mut [_] = unsafe { &mut *core::ptr::addr_of_mut!(FB) };
+            Some($crate::Framebuffer::<$format>::new(pixels, $width, $height, $width))
+        }
+    }};
+}
@@ -1,15 +1,45 @@
 #![no_std]
 
-#[cfg_attr(feature="real", link_section = ".fb")]
-static mut FB: [u8; FB_W*FB_H] = [0; FB_W*FB_H];
-
-#[inline(always)]
-pub fn fb() -> &'static mut [u8; FB_W*FB_H] {
-    unsafe { &mut FB }
-}
+mod deadline;
+mod dither;
+mod escape_time;
+mod fixed;
+mod float;
+mod formula;
+mod framebuffer;
+mod mandelbrot;
+mod overflow;
+mod palette;
+mod pixel;
+mod registry;
+mod scheduler;
+mod trap;
+
+use escape_time::{escape_time, escape_time_lockstep};
+use fixed::log2_q8;
+use formula::Quadratic;
+use scheduler::{render_rows, Lockstep, Symmetry};
+
+pub use dither::{Bayer, Diffusion, Levels};
+pub use escape_time::{Colouring, EscapeTime, Interior, Plane, Style, Styled};
+pub use fixed::{cos_sin, Fixed, Fx32, Fx64};
+pub use float::F32;
+pub use formula::{BurningShip, Formula, Multibrot, Phoenix, Symmetries, Tricorn};
+pub use framebuffer::Framebuffer;
+pub use mandelbrot::Mandelbrot;
+pub use overflow::{Op, Overflow};
+pub use palette::{tables, Animator, Clut, Cosine, Effect, Envelope, Gradient, Grayscale, HsvSweep, Palette, Table, DUSK, FIRE, TWO_TONE};
+pub use pixel::{Al44, Argb4444, Argb8888, Colours, PixelFormat, Rgb565, TrueColour, L8};
+pub use registry::Registry;
+pub use scheduler::Fill;
+pub use trap::{Trap, TrapShape};
 
 pub trait Context {
     fn wait_for_line(&mut self, pixel_y: usize);
+    /// How many line periods are left before the beam reads out line
+    /// `pixel_y` of the next refresh, by which time the frame being rendered
+    /// must have it. Negative once that deadline has been missed.
+    fn lines_left(&mut self, pixel_y: usize) -> i32;
     fn set_lut(&mut self, i: u8, r: u8, g: u8, b: u8);
     fn stats_count_adds(&mut self, n: usize);
     fn stats_count_cmps(&mut self, n: usize);
@@ -19,216 +49,143 @@ pub trait Context {
     fn stats_count_divs(&mut self, n: usize);
     fn stats_count_fcvts(&mut self, n: usize);
     fn stats_count_fmuls(&mut self, n: usize);
+    /// Pixels computed by a demo's kernel, and pixels filled in from
+    /// neighbouring computed ones instead (see `Fill`).
+    fn stats_count_computed(&mut self, n: usize);
+    fn stats_count_interpolated(&mut self, n: usize);
+    /// Iterations that a kernel didn't need to run because the orbit was
+    /// found to be periodic.
+    fn stats_count_saved_iters(&mut self, n: usize);
+
+    /// Called when fixed-point arithmetic overflows, which is only checked
+    /// for with the `checked` feature.
+    fn report_overflow(&mut self, _overflow: &Overflow) {}
 }
 
-pub trait Demo {
+/// An effect that renders into framebuffers in pixel format `F`.
+pub trait Demo<F: PixelFormat = L8> {
     /// Called as soon as possible once the vertical blanking period before
     /// frame F starts getting read out and frame F+1 starts getting computed by
-    /// render. Use to set up the colour LUT for frame F.
-    fn pre_render(&mut self, context: &mut dyn Context);
+    /// render. Use to set up the colour LUT for frame F, if `F` has one.
+    ///
+    /// The host keeps a `Clut` for the demo it shows, which is handed to both
+    /// calls.
+    fn pre_render(&mut self, context: &mut dyn Context, clut: &mut Clut);
 
     /// Called as soon as possible once the active area starts (and hence frame
-    /// F is getting read out). Use to render frame F+1.
-    fn render(&mut self, context: &mut dyn Context);
+    /// F is getting read out). Use to render frame F+1 into `fb`, at whatever
+    /// size it is.
+    ///
+    /// A double-buffered host hands over the buffer that isn't shown, and
+    /// only shows it (and calls `pre_render` for it) once `render` returns,
+    /// so its `wait_for_line` returns straight away and `lines_left` never
+    /// runs out.
+    fn render(&mut self, context: &mut dyn Context, clut: &mut Clut, fb: &mut Framebuffer<F>);
 }
 
-pub const FB_W: usize = 480;
-pub const FB_H: usize = 272;
-
-const Q: i32 = 10;
 const FRAME_MAX: u32 = 300;
 
-fn sin_internal(offset: i32) -> i32 {
-    assert!(offset >= 0 && offset <= (1<<Q));
-    match Q {
-        8 => offset * ((3<<16) - offset*offset) >> 17,
-        9 => offset * ((3<<18) - offset*offset) >> 19,
-        10 => (offset>>1) * ((3<<18) - (offset>>1)*(offset>>1)) >> 18,
-        11 => (offset>>2) * ((3<<18) - (offset>>2)*(offset>>2)) >> 17,
-        12 => (offset>>3) * ((3<<18) - (offset>>3)*(offset>>3)) >> 16,
-        13 => (offset>>4) * ((3<<18) - (offset>>4)*(offset>>4)) >> 15,
-        _ => unreachable!()
-    }
+/// The Julia set of z^2 + c, for c going round a circle, computed with
+/// fixed-point numbers of type `T` (or floats, with `F32`).
+///
+/// Pixels are iterated two at a time in lockstep where the fill allows,
+/// unless turned off with `with_lockstep`, which only exists to compare
+/// against.
+pub struct Julia<T: Fixed = Fx32<10>> {
+    frame: u32,
+    style: Style,
+    lockstep: bool,
+    _precision: core::marker::PhantomData<T>,
 }
 
-fn cos_sin(theta: i32) -> (i32, i32) {
-    assert!(theta >= 0 && theta <= (4<<Q));
-    if theta <= 1<<Q {
-        (sin_internal((1<<Q) - theta), sin_internal(theta))
-    } else if theta <= 2<<Q {
-        (-sin_internal(theta - (1<<Q)), sin_internal((2<<Q) - theta))
-    } else if theta <= 3<<Q {
-        (-sin_internal((3<<Q) - theta), -sin_internal(theta - (2<<Q)))
-    } else {
-        (sin_internal(theta - (3<<Q)), -sin_internal((4<<Q) - theta))
+impl<T: Fixed> Julia<T> {
+    pub fn new() -> Self {
+        Self { frame: 0, style: Style::new(), lockstep: true, _precision: core::marker::PhantomData }
     }
-}
 
-pub struct Julia {
-    frame: u32,
-}
+    pub fn with_lockstep(self, lockstep: bool) -> Self {
+        Self { lockstep, ..self }
+    }
 
-impl Julia {
-    pub fn new() -> Self {
-        Self { frame: 0 }
+    /// Where pixel (x, y) is in the plane.
+    #[inline(always)]
+    fn point(context: &mut dyn Context, frame: &JuliaFrame<T>, (pixel_x, pixel_y): (usize, usize)) -> (T, T) {
+        let fb_size = core::cmp::min(frame.fb_w, frame.fb_h) as i32;
+        let a = T::from_int(2*pixel_x as i32 - (frame.fb_w as i32 - 1)).div_int(fb_size);
+        let b = T::from_int(2*pixel_y as i32 - (frame.fb_h as i32 - 1)).div_int(fb_size);
+        T::count_cvts(context, 2);
+        (a, b)
+    }
+
+    #[inline(always)]
+    fn compute_value(&self, context: &mut dyn Context, frame: &JuliaFrame<T>, pixel_x: usize, pixel_y: usize, iter_limit: i32) -> u8 {
+        let z = Self::point(context, frame, (pixel_x, pixel_y));
+        escape_time(&Quadratic, context, (pixel_x, pixel_y), z, frame.c, iter_limit, frame.colouring, self.style.interior, frame.pixel_size)
     }
 
     #[inline(always)]
-    fn compute_value_hot(&self, context: &mut dyn Context, pixel_x: usize, pixel_y: usize, c_a: i32, c_b: i32) -> u8 {
-        let fb_size = core::cmp::min(FB_W, FB_H) as i32;
-        let mut a = (((pixel_x as i32) << Q) - ((FB_W as i32 - 1) << (Q-1))) * 2 / fb_size;
-        let mut b = (((pixel_y as i32) << Q) - ((FB_H as i32 - 1) << (Q-1))) * 2 / fb_size;
-        const ITER_MAX: i32 = 36;
-        let mut final_iter = ITER_MAX<<Q;
-        let mut prev_dist = -40<<Q;
-
-        for iter in 0..ITER_MAX {
-            context.stats_count_muls(1);
-            context.stats_count_shrs(1);
-            let a2 = a*a >> Q;
-
-            context.stats_count_muls(1);
-            context.stats_count_shrs(1);
-            let b2 = b*b >> Q;
-
-            context.stats_count_adds(1);
-            let this_dist = a2+b2;
-
-            context.stats_count_cmps(1);
-            if this_dist >= (4<<Q) {
-
-                context.stats_count_adds(2);
-                context.stats_count_shrs(2);
-                context.stats_count_divs(1);
-                let lerp = ((this_dist - (4<<Q)) << 8) / ((this_dist - prev_dist) >> (Q-8));
-
-                context.stats_count_adds(1);
-                context.stats_count_shrs(1);
-                final_iter = (iter << Q) - lerp;
-                break;
-            }
-
-            context.stats_count_muls(1);
-            context.stats_count_shrs(1);
-            let two_ab = a*b >> (Q-1);
-
-            context.stats_count_adds(2);
-            a = a2 - b2 + c_a;
-
-            context.stats_count_adds(1);
-            b = two_ab + c_b;
-
-            prev_dist = this_dist;
+    fn compute_pair(&self, context: &mut dyn Context, frame: &JuliaFrame<T>, pixels: [(usize, usize); 2], iter_limit: i32) -> [u8; 2] {
+        if !self.lockstep {
+            return pixels.map(|(pixel_x, pixel_y)| self.compute_value(context, frame, pixel_x, pixel_y, iter_limit));
         }
-        ((final_iter * 255) / (ITER_MAX << Q)) as u8
+        let mut pending = [overflow::Pending::default(); 2];
+        let z = core::array::from_fn(|lane| {
+            let z = Self::point(context, frame, pixels[lane]);
+            pending[lane] = overflow::set_aside();
+            z
+        });
+        escape_time_lockstep(&Quadratic, context, pixels, z, pending, [frame.c; 2], iter_limit, frame.colouring, self.style.interior, frame.pixel_size)
     }
+}
+
+/// What `Julia` works out once per frame for its pixels.
+struct JuliaFrame<T> {
+    c: (T, T),
+    colouring: Colouring,
+    fb_w: usize,
+    fb_h: usize,
+    /// log2 of the distance between pixels, in Q8: they are
+    /// 2 / min(fb_w, fb_h) apart.
+    pixel_size: i32,
+}
 
-    #[inline(never)]
-    fn compute_value_cold(&self, context: &mut dyn Context, pixel_x: usize, pixel_y: usize, c_a: i32, c_b: i32) -> u8 {
-        self.compute_value_hot(context, pixel_x, pixel_y, c_a, c_b)
+impl<T: Fixed> Default for Julia<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl Demo for Julia {
-    fn pre_render(&mut self, context: &mut dyn Context) {
-        for i in 0x00u32..=0xFFu32 {
-            let h = (((self.frame * 360))/FRAME_MAX + i) % 360;
-            let s = if i < 0xFF { 256-i } else { i };
-            let (_, sin) = cos_sin((2*i << Q) as i32 / 256);
-            let v = ((sin * 256) >> Q) as u32;
-
-            let h_sector = h / 60;
-            let h_frac = h % 60;
-
-            let p = v * ( 256 - s ) / 256;
-            let q = v * ( 256*60 - s * h_frac ) / (256*60);
-            let t = v * ( 256*60 - s * ( 60 - h_frac ) ) / (256*60);
-
-            let (r, g, b) = match h_sector {
-                0 => (v, t, p),
-                1 => (q, v, p),
-                2 => (p, v, t),
-                3 => (p, q, v),
-                4 => (t, p, v),
-                5 => (v, p, q),
-                _ => unreachable!()
-            };
-            let clamp = |x: u32| { if x > 255 { 255 } else { x } };
-            let r = clamp(r);
-            let g = clamp(g);
-            let b = clamp(b);
-            context.set_lut(i as u8, r as u8, g as u8, b as u8);
-        }
+impl<T: Fixed> Styled for Julia<T> {
+    fn style_mut(&mut self) -> &mut Style {
+        &mut self.style
+    }
+}
+
+impl<T: Fixed, F: PixelFormat> Demo<F> for Julia<T> {
+    fn pre_render(&mut self, context: &mut dyn Context, clut: &mut Clut) {
+        self.style.pre_render::<F>(context, clut);
     }
-    fn render(&mut self, context: &mut dyn Context) {
+    fn render(&mut self, context: &mut dyn Context, clut: &mut Clut, fb: &mut Framebuffer<F>) {
         self.frame += 1;
         if self.frame >= FRAME_MAX {
             self.frame = 0;
         }
-
-        let coeff = (0.7885 * (1<<Q) as f32) as i32;
-        let (cos, sin) = cos_sin(((4 * self.frame as i32) << Q) / FRAME_MAX as i32);
-        let c_a = (coeff * cos) >> Q;
-        let c_b = (coeff * sin) >> Q;
-        let average_value = |fb: &[u8; FB_W*FB_H], pixel_x, pixel_y| {
-            ((fb[(pixel_y-1) * FB_W + pixel_x] as u32
-              + fb[(pixel_y+1) * FB_W + pixel_x] as u32
-              + fb[(pixel_y+0) * FB_W + pixel_x-1] as u32
-              + fb[(pixel_y+0) * FB_W + pixel_x+1] as u32)
-             / 4) as u8
-        };
-        {
-            let pixel_y = 0;
-            context.wait_for_line(pixel_y);
-            for pixel_x in 0..FB_W {
-                let value = self.compute_value_cold(context, pixel_x, pixel_y, c_a, c_b);
-                fb()[pixel_y * FB_W + pixel_x] = value;
-            }
-        }
-        for pixel_y in 1..FB_H/2+1 {
-            context.wait_for_line(pixel_y);
-            if pixel_y < FB_H/2 {
-                let mut pixel_x = pixel_y & 1;
-                while pixel_x < FB_W {
-                    let value = self.compute_value_hot(context, pixel_x, pixel_y, c_a, c_b);
-                    fb()[pixel_y * FB_W + pixel_x] = value;
-                    pixel_x += 2;
-                }
-            }
-            if pixel_y >= 2 {
-                let pixel_y = pixel_y - 1;
-                let mut pixel_x = (pixel_y & 1) ^ 1;
-                while pixel_x < FB_W {
-                    let value = average_value(&fb(), pixel_x, pixel_y);
-                    fb()[pixel_y * FB_W + pixel_x] = value;
-                    pixel_x += 2;
-                }
-            }
-        }
-        {
-            let pixel_y = FB_H/2;
-            context.wait_for_line(pixel_y);
-            let mut pixel_x = pixel_y & 1;
-            while pixel_x < FB_W {
-                fb()[pixel_y * FB_W + pixel_x] = fb()[(FB_H - pixel_y - 1) * FB_W + FB_W - pixel_x - 1];
-                pixel_x += 2;
-            }
-        }
-        {
-            let pixel_y = FB_H/2-1;
-            let mut pixel_x = (pixel_y & 1) ^ 1;
-            while pixel_x < FB_W {
-                let value = average_value(&fb(), pixel_x, pixel_y);
-                fb()[pixel_y * FB_W + pixel_x] = value;
-                fb()[(FB_H - pixel_y - 1) * FB_W + FB_W - pixel_x - 1] = value;
-                pixel_x += 2;
-            }
-        }
-        for pixel_y in FB_H/2+1..FB_H {
-            context.wait_for_line(pixel_y);
-            for pixel_x in 0..FB_W {
-                fb()[pixel_y * FB_W + pixel_x] = fb()[(FB_H - pixel_y - 1) * FB_W + FB_W - pixel_x - 1];
-            }
-        }
+        let colours = self.style.prepare(clut);
+
+        let coeff = T::from_f32(0.7885);
+        let (cos, sin) = cos_sin(T::from_int(4 * self.frame as i32).div_int(FRAME_MAX as i32));
+        let (fb_w, fb_h) = (fb.width(), fb.height());
+        let pixel_size = (1 << 8) - log2_q8((core::cmp::min(fb_w, fb_h) as i32) << 8);
+        let frame = JuliaFrame { c: (coeff * cos, coeff * sin), colouring: self.style.colouring.at(self.frame), fb_w, fb_h, pixel_size };
+        // z^2 + c is even in z, whatever c is
+        let kernel = Lockstep(
+            |context: &mut dyn Context, pixel_x, pixel_y, iter_limit| {
+                F::from_index(self.compute_value(context, &frame, pixel_x, pixel_y, iter_limit), colours)
+            },
+            |context: &mut dyn Context, pixels, iter_limit| {
+                self.compute_pair(context, &frame, pixels, iter_limit).map(|index| F::from_index(index, colours))
+            },
+        );
+        render_rows(context, fb, self.frame, frame.colouring.restrict(Symmetry::Rotate180), self.style.fill, kernel);
     }
 }
@@ -0,0 +1,122 @@
+use crate::escape_time::{Plane, Style, Styled, View, STEP_EXTRA};
+use crate::formula::Quadratic;
+use crate::{Clut, Context, Demo, Fixed, Framebuffer, Fx32, Fx64, PixelFormat};
+
+/// A point on the zoom path: the view is centred on `re + im*i` and is
+/// `height` units tall. The view holds still for `hold` frames and then
+/// travels to the next keyframe over `travel` frames.
+struct Keyframe {
+    re: f32,
+    im: f32,
+    height: f32,
+    hold: u32,
+    travel: u32,
+}
+
+/// Tours a few boundary regions, returning to the overview in between. Views
+/// centred on the real axis are mirrored and so render twice as fast. The last
+/// stop is too deep for `Fx32<10>`, so it is rendered in `Fx64`, and deeper
+/// still would need more than `ITER_MAX` iterations to show anything.
+const PATH: &[Keyframe] = &[
+    Keyframe { re: -0.6, im: 0.0, height: 2.6, hold: 60, travel: 150 },
+    // Seahorse valley
+    Keyframe { re: -0.745, im: 0.11, height: 0.4, hold: 90, travel: 150 },
+    Keyframe { re: -0.6, im: 0.0, height: 2.6, hold: 30, travel: 150 },
+    // Elephant valley
+    Keyframe { re: 0.285, im: 0.01, height: 0.4, hold: 90, travel: 150 },
+    Keyframe { re: -0.6, im: 0.0, height: 2.6, hold: 30, travel: 150 },
+    // The neck of the period-2 bulb
+    Keyframe { re: -1.25, im: 0.0, height: 0.4, hold: 90, travel: 150 },
+    Keyframe { re: -0.6, im: 0.0, height: 2.6, hold: 30, travel: 150 },
+    // The filaments off the top of the main cardioid, about 1000 times in.
+    // The zoom goes in and out from straight above them, as the centre would
+    // otherwise lag behind the magnification and pass over the interior.
+    Keyframe { re: -0.1011, im: 0.9563, height: 1.0, hold: 0, travel: 240 },
+    Keyframe { re: -0.1011, im: 0.9563, height: 0.003, hold: 90, travel: 240 },
+    Keyframe { re: -0.1011, im: 0.9563, height: 1.0, hold: 0, travel: 150 },
+];
+
+/// The Mandelbrot set, computed with fixed-point numbers of type `T`, or of
+/// the more precise but slower type `D` where the zoom calls for it.
+pub struct Mandelbrot<T: Fixed = Fx32<10>, D: Fixed = Fx64<36>> {
+    frame: u32,
+    style: Style,
+    _precision: core::marker::PhantomData<(T, D)>,
+}
+
+impl<T: Fixed, D: Fixed> Mandelbrot<T, D> {
+    pub fn new() -> Self {
+        Self { frame: 0, style: Style::new(), _precision: core::marker::PhantomData }
+    }
+
+    fn path_frames() -> u32 {
+        PATH.iter().map(|k| k.hold + k.travel).sum()
+    }
+
+    fn view<U: Fixed>(&self, fb: &Framebuffer<impl PixelFormat>) -> View<U> {
+        let fx = U::from_f32;
+
+        let mut frame = self.frame;
+        let mut index = 0;
+        while frame >= PATH[index].hold + PATH[index].travel {
+            frame -= PATH[index].hold + PATH[index].travel;
+            index += 1;
+        }
+        let from = &PATH[index];
+        let to = &PATH[(index + 1) % PATH.len()];
+
+        // t runs from 0 to 1 over the travel, eased in and out
+        let t = if frame < from.hold { U::ZERO } else { U::from_int((frame - from.hold) as i32).div_int(from.travel as i32) };
+        let t = t * t * (U::from_int(3) - (t << 1));
+        let lerp = |x0: U, x1: U| x0 + (x1 - x0) * t;
+
+        // Interpolate the magnification rather than the height, so the zoom
+        // doesn't rush through the final stretch.
+        let magnification = lerp(fx(1.0 / from.height), fx(1.0 / to.height));
+        View {
+            re: lerp(fx(from.re), fx(to.re)),
+            im: lerp(fx(from.im), fx(to.im)),
+            step: ((U::ONE << STEP_EXTRA) / magnification).div_int(fb.height() as i32),
+            fb_w: fb.width(),
+            fb_h: fb.height(),
+        }
+    }
+}
+
+impl<T: Fixed, D: Fixed> Default for Mandelbrot<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Fixed, D: Fixed> Styled for Mandelbrot<T, D> {
+    fn style_mut(&mut self) -> &mut Style {
+        &mut self.style
+    }
+}
+
+impl<T: Fixed, D: Fixed, F: PixelFormat> Demo<F> for Mandelbrot<T, D> {
+    fn pre_render(&mut self, context: &mut dyn Context, clut: &mut Clut) {
+        self.style.pre_render::<F>(context, clut);
+    }
+
+    fn render(&mut self, context: &mut dyn Context, clut: &mut Clut, fb: &mut Framebuffer<F>) {
+        self.frame += 1;
+        if self.frame >= Self::path_frames() {
+            self.frame = 0;
+        }
+        let colours = self.style.prepare(clut);
+
+        // The view is worked out precisely first, to see whether `T` is up to
+        // it: only once pixels are less than one unit of `T` apart do
+        // neighbouring pixels land on the same point, and the slower `D`
+        // takes over.
+        let deep = self.view::<D>(fb);
+        if deep.pixel_size() < -((T::FRAC_BITS as i32) << 8) {
+            self.style.render_view(context, fb, colours, self.frame, &Quadratic, Plane::Parameter, &deep);
+        } else {
+            let view = self.view::<T>(fb);
+            self.style.render_view(context, fb, colours, self.frame, &Quadratic, Plane::Parameter, &view);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Overflow reporting for the `checked` feature.
+//!
+//! With the feature enabled, the fixed-point operators check for overflow,
+//! saturate, and note which operation overflowed; the kernels then pass that on
+//! to `Context::report_overflow` along with the pixel and iteration. Without
+//! it, they wrap, as the firmware relies on for speed.
+
+use crate::Context;
+
+/// A fixed-point operation that can overflow.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    Add,
+    Sub,
+    Neg,
+    Mul,
+    Shl,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Overflow {
+    pub pixel_x: usize,
+    pub pixel_y: usize,
+    pub iter: i32,
+    pub op: Op,
+}
+
+/// Evaluates to `$checked` (an `Option`) when the `checked` feature is
+/// enabled, recording an overflow and falling back to `$saturating` if it is
+/// `None`, and to `$wrapping` otherwise.
+macro_rules! arith {
+    ($op:expr, $checked:expr, $saturating:expr, $wrapping:expr) => {{
+        #[cfg(feature = "checked")]
+        let result = match $checked {
+            Some(x) => x,
+            None => {
+                crate::overflow::record($op);
+                $saturating
+            }
+        };
+        #[cfg(not(feature = "checked"))]
+        let result = {
+            let _ = $op;
+            $wrapping
+        };
+        result
+    }};
+}
+
+pub(crate) use arith;
+
+#[cfg(feature = "checked")]
+static PENDING: core::sync::atomic::AtomicU8 = core::sync::atomic::AtomicU8::new(0);
+
+/// Notes that `op` overflowed, unless an earlier overflow is still pending.
+#[cfg(feature = "checked")]
+pub(crate) fn record(op: Op) {
+    use core::sync::atomic::Ordering;
+    let _ = PENDING.compare_exchange(0, op as u8 + 1, Ordering::Relaxed, Ordering::Relaxed);
+}
+
+#[cfg(feature = "checked")]
+fn take() -> Option<Op> {
+    use core::sync::atomic::Ordering;
+    match PENDING.swap(0, Ordering::Relaxed) {
+        0 => None,
+        1 => Some(Op::Add),
+        2 => Some(Op::Sub),
+        3 => Some(Op::Neg),
+        4 => Some(Op::Mul),
+        _ => Some(Op::Shl),
+    }
+}
+
+/// An overflow noted but not yet checked, set aside by kernels that work on
+/// several pixels at once so that it is reported for the right one.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Pending(#[cfg(feature = "checked")] u8);
+
+/// Takes any overflow since the last check, to be put back with `restore`
+/// before the check for the pixel it belongs to.
+#[inline(always)]
+pub(crate) fn set_aside() -> Pending {
+    #[cfg(feature = "checked")]
+    return Pending(PENDING.swap(0, core::sync::atomic::Ordering::Relaxed));
+    #[cfg(not(feature = "checked"))]
+    Pending()
+}
+
+/// Puts back an overflow taken by `set_aside`, which, having come first,
+/// takes the place of any pending since.
+#[inline(always)]
+pub(crate) fn restore(pending: Pending) {
+    #[cfg(feature = "checked")]
+    if pending.0 != 0 {
+        PENDING.store(pending.0, core::sync::atomic::Ordering::Relaxed);
+    }
+    #[cfg(not(feature = "checked"))]
+    let _ = pending;
+}
+
+/// Reports any overflow since the last check as having happened at `iter` of
+/// the given pixel. Compiles to nothing without the `checked` feature.
+#[inline(always)]
+pub(crate) fn check(context: &mut dyn Context, (pixel_x, pixel_y): (usize, usize), iter: i32) {
+    #[cfg(feature = "checked")]
+    if let Some(op) = take() {
+        context.report_overflow(&Overflow { pixel_x, pixel_y, iter, op });
+    }
+    #[cfg(not(feature = "checked"))]
+    let _ = (context, pixel_x, pixel_y, iter);
+}
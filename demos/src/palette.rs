@@ -0,0 +1,330 @@
+//! Colour palettes for the demos, shown through the CLUT or looked up while
+//! rendering (see `PixelFormat`).
+//!
+//! Demos hold an `Animator`, which pairs a `&'static dyn Palette` with any
+//! `Effect`s and writes the result out in `pre_render` through the host's
+//! `Clut`, so any of them can be paired with any palette.
+
+use crate::{cos_sin, Colours, Context, Fixed, Fx32, PixelFormat, FRAME_MAX};
+
+/// A colour LUT, possibly animated over the `FRAME_MAX` frame loop.
+///
+/// Palettes are `Sync` so that demos holding them can be shared with the
+/// firmware's interrupt handler.
+pub trait Palette: Sync {
+    /// The colour of entry `i` at frame `t`.
+    fn colour(&self, i: u8, t: u32) -> (u8, u8, u8);
+}
+
+/// Sweeps the hue around, starting further round each frame, fading to black
+/// at both ends of the index range.
+pub struct HsvSweep;
+
+impl Palette for HsvSweep {
+    fn colour(&self, i: u8, frame: u32) -> (u8, u8, u8) {
+        let i = i as u32;
+        let h = ((frame * 360)/FRAME_MAX + i) % 360;
+        let s = if i < 0xFF { 256-i } else { i };
+        let (_, sin) = cos_sin(Fx32::<10>::from_q(2*i as i32, 8));
+        let v = sin.to_q(8) as u32;
+
+        let h_sector = h / 60;
+        let h_frac = h % 60;
+
+        let p = v * ( 256 - s ) / 256;
+        let q = v * ( 256*60 - s * h_frac ) / (256*60);
+        let t = v * ( 256*60 - s * ( 60 - h_frac ) ) / (256*60);
+
+        let (r, g, b) = match h_sector {
+            0 => (v, t, p),
+            1 => (q, v, p),
+            2 => (p, v, t),
+            3 => (p, q, v),
+            4 => (t, p, v),
+            5 => (v, p, q),
+            _ => unreachable!()
+        };
+        let clamp = |x: u32| { if x > 255 { 255 } else { x } };
+        (clamp(r) as u8, clamp(g) as u8, clamp(b) as u8)
+    }
+}
+
+/// Linear interpolation between colour stops, the first at index 0 and the
+/// last at index 255.
+pub struct Gradient {
+    stops: &'static [(u8, (u8, u8, u8))],
+}
+
+impl Gradient {
+    pub const fn new(stops: &'static [(u8, (u8, u8, u8))]) -> Self {
+        assert!(stops.len() >= 2 && stops[0].0 == 0 && stops[stops.len() - 1].0 == 255);
+        let mut i = 1;
+        while i < stops.len() {
+            assert!(stops[i - 1].0 < stops[i].0);
+            i += 1;
+        }
+        Self { stops }
+    }
+}
+
+impl Palette for Gradient {
+    fn colour(&self, i: u8, _t: u32) -> (u8, u8, u8) {
+        let n = self.stops.iter().position(|&(index, _)| index >= i).unwrap();
+        let (i1, c1) = self.stops[n];
+        if n == 0 {
+            return c1;
+        }
+        let (i0, c0) = self.stops[n - 1];
+        let lerp = |x0: u8, x1: u8| (x0 as i32 + (x1 as i32 - x0 as i32) * (i - i0) as i32 / (i1 - i0) as i32) as u8;
+        (lerp(c0.0, c1.0), lerp(c0.1, c1.1), lerp(c0.2, c1.2))
+    }
+}
+
+/// Black through red and yellow to white.
+pub const FIRE: Gradient = Gradient::new(&[
+    (0, (0, 0, 0)),
+    (96, (192, 16, 0)),
+    (160, (255, 128, 0)),
+    (224, (255, 240, 64)),
+    (255, (255, 255, 255)),
+]);
+
+/// Shades of blue in the lower half and of orange in the upper half, for
+/// `Colouring::Decomposition`, with black for the interior.
+pub const TWO_TONE: Gradient = Gradient::new(&[
+    (0, (0, 16, 64)),
+    (127, (160, 208, 255)),
+    (128, (64, 16, 0)),
+    (254, (255, 224, 160)),
+    (255, (0, 0, 0)),
+]);
+
+/// a + b*cos(2pi(c*x + d)) per channel, for x = i/256, as popularised by
+/// Inigo Quilez. The phase also goes round once per loop.
+pub struct Cosine {
+    /// a, b, c and d, in Q8.
+    coeffs: [[i32; 3]; 4],
+}
+
+impl Cosine {
+    pub const fn new(a: [f32; 3], b: [f32; 3], c: [f32; 3], d: [f32; 3]) -> Self {
+        const fn q8(x: [f32; 3]) -> [i32; 3] {
+            [(x[0] * 256.0) as i32, (x[1] * 256.0) as i32, (x[2] * 256.0) as i32]
+        }
+        Self { coeffs: [q8(a), q8(b), q8(c), q8(d)] }
+    }
+}
+
+impl Palette for Cosine {
+    fn colour(&self, i: u8, t: u32) -> (u8, u8, u8) {
+        let [a, b, c, d] = self.coeffs;
+        let channel = |n: usize| {
+            let phase = ((c[n] * i as i32) >> 8) + d[n] + (t << 8) as i32 / FRAME_MAX as i32;
+            let (cos, _) = cos_sin(Fx32::<14>::from_q(4 * (phase & 0xFF), 8));
+            (a[n] + ((b[n] * cos.0) >> 14)).clamp(0, 255) as u8
+        };
+        (channel(0), channel(1), channel(2))
+    }
+}
+
+/// Pale gold through dark blue and back.
+pub const DUSK: Cosine = Cosine::new([0.5, 0.5, 0.5], [0.5, 0.5, 0.5], [1.0, 1.0, 1.0], [0.0, 0.1, 0.2]);
+
+pub struct Grayscale;
+
+impl Palette for Grayscale {
+    fn colour(&self, i: u8, _t: u32) -> (u8, u8, u8) {
+        (i, i, i)
+    }
+}
+
+/// A fixed LUT, such as those `build.rs` generates from `palettes/` into
+/// `tables`.
+pub struct Table(pub [(u8, u8, u8); 256]);
+
+impl Palette for Table {
+    fn colour(&self, i: u8, _t: u32) -> (u8, u8, u8) {
+        self.0[i as usize]
+    }
+}
+
+/// The palettes imported from the GIMP (`.gpl`) and Fractint (`.map`) files
+/// in `palettes/`.
+pub mod tables {
+    use super::Table;
+
+    include!(concat!(env!("OUT_DIR"), "/palettes.rs"));
+}
+
+/// How strongly an effect applies over the loop: ramping in over `ramp`
+/// frames from frame `start`, holding for `hold` frames, then ramping out
+/// again over `ramp` frames.
+#[derive(Clone, Copy)]
+pub struct Envelope {
+    pub start: u32,
+    pub ramp: u32,
+    pub hold: u32,
+}
+
+impl Envelope {
+    /// The weight at frame `t`, in Q8.
+    fn weight(&self, t: u32) -> u32 {
+        let Envelope { start, ramp, hold } = *self;
+        if t < start {
+            return 0;
+        }
+        let t = t - start;
+        if t < ramp {
+            (t << 8) / ramp
+        } else if t < ramp + hold {
+            1 << 8
+        } else if t < 2*ramp + hold {
+            ((2*ramp + hold - t) << 8) / ramp
+        } else {
+            0
+        }
+    }
+}
+
+/// A change to a palette over the loop, applied by `Animator`.
+#[derive(Clone, Copy)]
+pub enum Effect {
+    /// Rotates entries `first..=last` one place every `period` frames, as in
+    /// classic colour cycling, without starting again at the end of the loop.
+    /// A `period` of 0 rotates every frame, as 1 does.
+    Cycle { first: u8, last: u8, period: u32 },
+    /// Blends into another palette (a keyframe when `hold` lasts the rest of
+    /// the loop).
+    Crossfade { to: &'static dyn Palette, envelope: Envelope },
+    /// Blends into a flat colour, e.g. to fade to black or flash white.
+    Fade { colour: (u8, u8, u8), envelope: Envelope },
+}
+
+/// The CLUT as a host last had it written, and the colours of every palette
+/// index for the frame being rendered.
+///
+/// At 1.5 KB, this is more than the firmware's stack holds, so rather than
+/// every demo carrying one, the host keeps one for whatever it shows and
+/// hands it to `Demo::pre_render` and `Demo::render`.
+pub struct Clut {
+    shadow: Colours,
+    /// Whether `shadow` holds what was last written, which it won't until
+    /// every entry has been written once.
+    written: bool,
+    next: Colours,
+}
+
+impl Clut {
+    pub const fn new() -> Self {
+        Self { shadow: [(0, 0, 0); 256], written: false, next: [(0, 0, 0); 256] }
+    }
+}
+
+impl Default for Clut {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drives the CLUT from a palette and its effects.
+///
+/// Writing all 256 entries through `Context::set_lut` every frame takes a good
+/// part of the vertical blanking period, so only the entries that differ from
+/// the `Clut`'s shadow are written. The next frame's LUT can be worked out
+/// ahead of time with `prepare`, e.g. at the start of `render`, where
+/// direct-colour formats need the colours it returns anyway, so that only the
+/// writes are left for `pre_render`.
+///
+/// The animator counts its own frames, one per `prepare`, rather than taking
+/// the demo's, which start again at the end of a loop of whatever length the
+/// demo has: palettes and envelopes go round the `FRAME_MAX` frame loop, and
+/// cycles carry on past it.
+#[derive(Clone, Copy)]
+pub struct Animator {
+    palette: &'static dyn Palette,
+    effects: &'static [Effect],
+    /// Frames since the animator started, wrapping only after two years at
+    /// 60 Hz.
+    frame: u32,
+    /// Whether the `Clut`'s colours were prepared for `frame`.
+    prepared: bool,
+}
+
+impl Animator {
+    pub fn new(palette: &'static dyn Palette, effects: &'static [Effect]) -> Self {
+        Self { palette, effects, frame: 0, prepared: false }
+    }
+
+    pub fn with_palette(self, palette: &'static dyn Palette) -> Self {
+        Self { palette, prepared: false, ..self }
+    }
+
+    pub fn with_effects(self, effects: &'static [Effect]) -> Self {
+        Self { effects, prepared: false, ..self }
+    }
+
+    /// The colour of entry `i` at the current frame. Cycles move the entry
+    /// looked up in every palette, and blends apply in order on top.
+    fn colour(&self, i: u8) -> (u8, u8, u8) {
+        let t = self.frame % FRAME_MAX;
+        let mut i = i;
+        for effect in self.effects {
+            if let Effect::Cycle { first, last, period } = *effect {
+                if i >= first && i <= last {
+                    let len = (last - first) as u32 + 1;
+                    let offset = (self.frame / period.max(1)) % len;
+                    i = first + (((i - first) as u32 + offset) % len) as u8;
+                }
+            }
+        }
+
+        let blend = |(r0, g0, b0): (u8, u8, u8), (r1, g1, b1): (u8, u8, u8), w: u32| {
+            let mix = |x0: u8, x1: u8| (x0 as i32 + (((x1 as i32 - x0 as i32) * w as i32) >> 8)) as u8;
+            (mix(r0, r1), mix(g0, g1), mix(b0, b1))
+        };
+        let mut colour = self.palette.colour(i, t);
+        for effect in self.effects {
+            colour = match *effect {
+                Effect::Cycle { .. } => colour,
+                Effect::Crossfade { to, envelope } => match envelope.weight(t) {
+                    0 => colour,
+                    w => blend(colour, to.colour(i, t), w),
+                },
+                Effect::Fade { colour: to, envelope } => blend(colour, to, envelope.weight(t)),
+            };
+        }
+        colour
+    }
+
+    fn compute(&mut self, clut: &mut Clut) {
+        for i in 0x00..=0xFF {
+            clut.next[i as usize] = self.colour(i);
+        }
+        self.prepared = true;
+    }
+
+    /// Moves on to the next frame and works out its LUT into `clut`, ready for
+    /// `write`, returning its colours.
+    pub fn prepare<'a>(&mut self, clut: &'a mut Clut) -> &'a Colours {
+        self.frame = self.frame.wrapping_add(1);
+        self.compute(clut);
+        &clut.next
+    }
+
+    /// Writes the entries of the CLUT for the current frame in format `F` that
+    /// differ from what it holds, working out the colours first if `prepare`
+    /// hasn't.
+    pub fn write<F: PixelFormat>(&mut self, context: &mut dyn Context, clut: &mut Clut) {
+        if !self.prepared {
+            self.compute(clut);
+        }
+        for entry in 0..F::LUT_ENTRIES {
+            let (r, g, b) = clut.next[F::lut_index(entry) as usize];
+            if !clut.written || clut.shadow[entry] != (r, g, b) {
+                context.set_lut(entry as u8, r, g, b);
+                clut.shadow[entry] = (r, g, b);
+            }
+        }
+        clut.written = true;
+    }
+}
@@ -0,0 +1,202 @@
+//! The pixel formats that the LTDC can read a layer in.
+//!
+//! Demos work out a palette index for each pixel, which `PixelFormat::from_index`
+//! turns into a pixel: the index itself for L8, the top four bits of it for
+//! AL44 (whose 16 CLUT entries the `Animator` fills from the middle of each
+//! run of 16 colours), or the colour it stands for in the direct-colour
+//! formats, which don't use the CLUT at all.
+
+use core::fmt::Debug;
+
+/// The colours of the 256 palette indices for the frame being rendered.
+pub type Colours = [(u8, u8, u8); 256];
+
+/// How pixels are stored in a `Framebuffer`.
+pub trait PixelFormat: 'static {
+    /// One pixel, which is as many bytes as the format takes.
+    type Pixel: Copy + PartialEq + Default + Debug + Send;
+
+    /// How many CLUT entries the format indexes, or 0 for direct colour.
+    const LUT_ENTRIES: usize;
+
+    /// The pixel for palette index `index`.
+    fn from_index(index: u8, colours: &Colours) -> Self::Pixel;
+
+    /// The colour that `pixel` is shown as, given the CLUT.
+    fn decode(pixel: Self::Pixel, lut: &Colours) -> (u8, u8, u8);
+
+    /// The palette index whose colour CLUT entry `entry` holds, by default
+    /// the middle of an even share of the 256. Direct-colour formats have no
+    /// CLUT to share out, and override it.
+    fn lut_index(entry: usize) -> u8 {
+        let run = 256 / Self::LUT_ENTRIES;
+        (entry * run + run/2) as u8
+    }
+
+    /// The pixel in between four neighbours, for `Fill` to interpolate with.
+    fn average(pixels: [Self::Pixel; 4]) -> Self::Pixel;
+
+    /// How far apart the most different of four pixels are, in palette
+    /// indices or, for direct colour, the largest difference in a channel
+    /// out of 255.
+    fn spread(pixels: [Self::Pixel; 4]) -> u8;
+}
+
+/// Direct-colour formats, which can show any colour to within their
+/// precision rather than only those in the CLUT.
+pub trait TrueColour: PixelFormat {
+    /// The bits of red, green and blue.
+    const BITS: [u32; 3];
+
+    /// The nearest pixel to an opaque colour.
+    fn encode(r: u8, g: u8, b: u8) -> Self::Pixel;
+}
+
+/// 8-bit palette indices into the CLUT.
+pub struct L8;
+
+impl PixelFormat for L8 {
+    type Pixel = u8;
+    const LUT_ENTRIES: usize = 256;
+
+    #[inline(always)]
+    fn from_index(index: u8, _colours: &Colours) -> u8 {
+        index
+    }
+
+    fn decode(pixel: u8, lut: &Colours) -> (u8, u8, u8) {
+        lut[pixel as usize]
+    }
+
+    #[inline(always)]
+    fn average(pixels: [u8; 4]) -> u8 {
+        (pixels.iter().map(|&v| v as u32).sum::<u32>() / 4) as u8
+    }
+
+    #[inline(always)]
+    fn spread(pixels: [u8; 4]) -> u8 {
+        pixels.iter().max().unwrap() - pixels.iter().min().unwrap()
+    }
+}
+
+/// 4 bits of alpha over 4-bit indices into the first 16 CLUT entries. The
+/// pixels written are opaque.
+pub struct Al44;
+
+impl PixelFormat for Al44 {
+    type Pixel = u8;
+    const LUT_ENTRIES: usize = 16;
+
+    #[inline(always)]
+    fn from_index(index: u8, _colours: &Colours) -> u8 {
+        0xF0 | index >> 4
+    }
+
+    fn decode(pixel: u8, lut: &Colours) -> (u8, u8, u8) {
+        lut[(pixel & 0x0F) as usize]
+    }
+
+    #[inline(always)]
+    fn average(pixels: [u8; 4]) -> u8 {
+        0xF0 | (pixels.iter().map(|&v| (v & 0x0F) as u32).sum::<u32>() / 4) as u8
+    }
+
+    #[inline(always)]
+    fn spread(pixels: [u8; 4]) -> u8 {
+        let entries = pixels.map(|v| v & 0x0F);
+        (entries.iter().max().unwrap() - entries.iter().min().unwrap()) * 16
+    }
+}
+
+/// Declares a direct-colour format packing alpha, red, green and blue from
+/// the top bit down, with the given bits of each.
+macro_rules! direct {
+    ($(#[$attr:meta])* $name:ident, $pixel:ty, [$a:expr, $r:expr, $g:expr, $b:expr]) => {
+        $(#[$attr])*
+        pub struct $name;
+
+        impl $name {
+            const SIZES: [u32; 4] = [$a, $r, $g, $b];
+            const SHIFTS: [u32; 4] = [$r + $g + $b, $g + $b, $b, 0];
+
+            #[inline(always)]
+            fn channels(pixel: $pixel) -> [u32; 4] {
+                core::array::from_fn(|i| (pixel as u32 >> Self::SHIFTS[i]) & ((1 << Self::SIZES[i]) - 1))
+            }
+
+            #[inline(always)]
+            fn pack(channels: [u32; 4]) -> $pixel {
+                (0..4).map(|i| channels[i] << Self::SHIFTS[i]).fold(0, |pixel, c| pixel | c) as $pixel
+            }
+
+            /// Widens channel `i` to 8 bits, repeating its top bits in the
+            /// bits below so that full scale stays full scale.
+            #[inline(always)]
+            fn widen(channels: [u32; 4], i: usize) -> u8 {
+                let c = channels[i] << (8 - Self::SIZES[i]);
+                (c | c >> Self::SIZES[i]) as u8
+            }
+        }
+
+        impl PixelFormat for $name {
+            type Pixel = $pixel;
+            const LUT_ENTRIES: usize = 0;
+
+            /// There are no entries, so none stands for more than its own
+            /// index.
+            fn lut_index(entry: usize) -> u8 {
+                entry as u8
+            }
+
+            #[inline(always)]
+            fn from_index(index: u8, colours: &Colours) -> $pixel {
+                let (r, g, b) = colours[index as usize];
+                Self::encode(r, g, b)
+            }
+
+            fn decode(pixel: $pixel, _lut: &Colours) -> (u8, u8, u8) {
+                let channels = Self::channels(pixel);
+                (Self::widen(channels, 1), Self::widen(channels, 2), Self::widen(channels, 3))
+            }
+
+            #[inline(always)]
+            fn average(pixels: [$pixel; 4]) -> $pixel {
+                let channels = pixels.map(Self::channels);
+                Self::pack(core::array::from_fn(|i| channels.iter().map(|c| c[i]).sum::<u32>() / 4))
+            }
+
+            #[inline(always)]
+            fn spread(pixels: [$pixel; 4]) -> u8 {
+                let channels = pixels.map(Self::channels);
+                (1..4).map(|i| {
+                    let (min, max) = channels.iter().fold((u32::MAX, 0), |(min, max), c| (min.min(c[i]), max.max(c[i])));
+                    (max - min) << (8 - Self::SIZES[i])
+                }).max().unwrap() as u8
+            }
+        }
+
+        impl TrueColour for $name {
+            const BITS: [u32; 3] = [$r, $g, $b];
+
+            #[inline(always)]
+            fn encode(r: u8, g: u8, b: u8) -> $pixel {
+                let alpha = (1 << Self::SIZES[0]) - 1;
+                Self::pack([alpha, r as u32 >> (8 - $r), g as u32 >> (8 - $g), b as u32 >> (8 - $b)])
+            }
+        }
+    };
+}
+
+direct!(
+    /// 16-bit colour with no alpha: 5 bits of red, 6 of green and 5 of blue.
+    Rgb565, u16, [0, 5, 6, 5]
+);
+direct!(
+    /// 16-bit colour with 4 bits for each channel and alpha.
+    Argb4444, u16, [4, 4, 4, 4]
+);
+direct!(
+    /// 32-bit colour with 8 bits for each channel and alpha, which takes more
+    /// memory than the internal SRAM has for a full-screen framebuffer.
+    Argb8888, u32, [8, 8, 8, 8]
+);
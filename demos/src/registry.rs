@@ -0,0 +1,122 @@
+use crate::tables::{SUNSET, VOLCANO};
+use crate::{BurningShip, Clut, Colouring, Context, Demo, Effect, Envelope, EscapeTime, F32, Fill, Framebuffer, Fx64, Grayscale, Interior, Julia, Mandelbrot, Multibrot, Phoenix, PixelFormat, Plane, Styled, Trap, TrapShape, Tricorn, DUSK, FIRE, TWO_TONE};
+
+/// Declares `AnyDemo`, which holds any one of the listed demos without
+/// needing an allocator, along with the name and constructor of each.
+macro_rules! registry {
+    ($($variant:ident($ty:ty) = $name:literal => $ctor:expr,)*) => {
+        enum AnyDemo {
+            $($variant($ty),)*
+        }
+
+        const ENTRIES: &[(&str, fn() -> AnyDemo)] = &[
+            $(($name, || AnyDemo::$variant($ctor)),)*
+        ];
+
+        impl AnyDemo {
+            fn as_demo<F: PixelFormat>(&mut self) -> &mut dyn Demo<F> {
+                match self {
+                    $(AnyDemo::$variant(demo) => demo,)*
+                }
+            }
+        }
+    };
+}
+
+registry! {
+    Julia(Julia) = "julia" => Julia::new(),
+    JuliaQ16(Julia<Fx64<16>>) = "julia-q16" => Julia::new(),
+    JuliaF32(Julia<F32>) = "julia-f32" => Julia::new(),
+    JuliaSharp(Julia) = "julia-sharp" => Julia::new().with_fill(Fill::EdgeAware { threshold: 16 }),
+    JuliaDistance(Julia) = "julia-distance" => Julia::new().with_colouring(Colouring::Distance).with_palette(&Grayscale),
+    JuliaTrap(Julia) = "julia-trap" => Julia::new().with_colouring(Colouring::Trap(Trap::new(TrapShape::Cross, 0.0, 0.0, 0.25).with_drift(0.3)))
+        .with_palette(&DUSK),
+    JuliaFieldLines(Julia) = "julia-field-lines" => Julia::new().with_colouring(Colouring::Decomposition { sectors: 8 })
+        .with_fill(Fill::EdgeAware { threshold: 64 }).with_palette(&TWO_TONE),
+    Mandelbrot(Mandelbrot) = "mandelbrot" => Mandelbrot::new().with_fill(Fill::Subdivide).with_colouring(Colouring::Smooth)
+        .with_effects(&[Effect::Crossfade { to: &DUSK, envelope: Envelope { start: 120, ramp: 30, hold: 60 } }]),
+    MandelbrotPeriods(Mandelbrot) = "mandelbrot-periods" => Mandelbrot::new().with_fill(Fill::Subdivide).with_colouring(Colouring::Smooth)
+        .with_interior(Interior::Period),
+    MandelbrotBinary(Mandelbrot) = "mandelbrot-binary" => Mandelbrot::new().with_colouring(Colouring::Decomposition { sectors: 2 })
+        .with_fill(Fill::EdgeAware { threshold: 64 }).with_palette(&TWO_TONE),
+    BurningShip(EscapeTime<BurningShip>) = "burning-ship" => EscapeTime::new(BurningShip, Plane::Parameter, -0.45, -0.5, 2.2).with_palette(&FIRE)
+        .with_effects(&[Effect::Cycle { first: 0, last: 254, period: 1 }]),
+    Tricorn(EscapeTime<Tricorn>) = "tricorn" => EscapeTime::new(Tricorn, Plane::Parameter, -0.3, 0.0, 3.2).with_palette(&DUSK),
+    Multibrot3(EscapeTime<Multibrot<3>>) = "multibrot3" => EscapeTime::new(Multibrot, Plane::Parameter, 0.0, 0.0, 3.0).with_fill(Fill::Subdivide).with_colouring(Colouring::Smooth)
+        .with_palette(&VOLCANO),
+    Phoenix(EscapeTime<Phoenix>) = "phoenix" => EscapeTime::new(Phoenix::new(), Plane::dynamic(0.5667, 0.0), 0.0, 0.0, 2.6).with_palette(&SUNSET),
+}
+
+/// Every `Demo` in this crate, with one of them running at a time.
+///
+/// The registry is itself a `Demo` that forwards to whichever demo is
+/// currently selected, so hosts drive it exactly like a single demo and call
+/// `select`, `next` or `prev` to change effects. Like the demos themselves, it
+/// renders in any `PixelFormat`.
+pub struct Registry {
+    index: usize,
+    demo: AnyDemo,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self { index: 0, demo: (ENTRIES[0].1)() }
+    }
+
+    /// The names of all demos, in registry order.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        ENTRIES.iter().map(|(name, _)| *name)
+    }
+
+    pub fn len() -> usize {
+        ENTRIES.len()
+    }
+
+    pub fn find(name: &str) -> Option<usize> {
+        Self::names().position(|n| n == name)
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn name(&self) -> &'static str {
+        ENTRIES[self.index].0
+    }
+
+    /// Replaces the running demo with a freshly constructed demo `index`, and
+    /// calls its `pre_render` straight away so the colour LUT is valid before
+    /// its first frame is shown. Only call this where `pre_render` may be
+    /// called, i.e. during the vertical blanking period, and with the format
+    /// that will be rendered in.
+    pub fn select<F: PixelFormat>(&mut self, index: usize, context: &mut dyn Context, clut: &mut Clut) {
+        assert!(index < ENTRIES.len());
+        self.index = index;
+        self.demo = (ENTRIES[index].1)();
+        self.demo.as_demo::<F>().pre_render(context, clut);
+    }
+
+    pub fn next<F: PixelFormat>(&mut self, context: &mut dyn Context, clut: &mut Clut) {
+        self.select::<F>((self.index + 1) % ENTRIES.len(), context, clut);
+    }
+
+    pub fn prev<F: PixelFormat>(&mut self, context: &mut dyn Context, clut: &mut Clut) {
+        self.select::<F>((self.index + ENTRIES.len() - 1) % ENTRIES.len(), context, clut);
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: PixelFormat> Demo<F> for Registry {
+    fn pre_render(&mut self, context: &mut dyn Context, clut: &mut Clut) {
+        self.demo.as_demo::<F>().pre_render(context, clut);
+    }
+
+    fn render(&mut self, context: &mut dyn Context, clut: &mut Clut, fb: &mut Framebuffer<F>) {
+        self.demo.as_demo().render(context, clut, fb);
+    }
+}
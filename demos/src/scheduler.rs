@@ -0,0 +1,383 @@
+//! Racing the beam down the screen with a per-pixel kernel.
+//!
+//! `render_rows` computes the rows in the order the beam reads them out,
+//! waiting for each line before writing it. Inside the part of the screen that
+//! the `Symmetry` doesn't give for free, only every other pixel of most rows is
+//! computed, in a checkerboard, and the rest are averaged from their four
+//! neighbours one line behind. `Fill::Subdivide` instead works in bands of
+//! rows, flooding rectangles whose borders come out uniform. The other parts
+//! are then copied across, the bottom half as the beam reaches it.
+//!
+//! Pixels are computed two at a time wherever that doesn't depend on the
+//! pixels before, so that a `Kernel` can iterate them in lockstep.
+
+use crate::deadline::{Budget, Quality};
+use crate::{Context, Framebuffer, PixelFormat};
+
+/// A symmetry of the image on screen, for a framebuffer `width` by `height`
+/// pixels. With an odd number of columns or rows, the middle one maps onto
+/// itself.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    /// Pixel (x, y) is pixel `(width - 1 - x, y)`.
+    FlipX,
+    /// Row y is row `height - 1 - y`.
+    FlipY,
+    /// Pixel (x, y) is pixel `(width - 1 - x, height - 1 - y)`.
+    Rotate180,
+    /// Both `FlipX` and `FlipY`, and so `Rotate180` too.
+    FourFold,
+}
+
+/// How the pixels that the symmetry doesn't give for free are filled in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fill {
+    /// Computes every other pixel and averages the rest from their
+    /// neighbours, which always takes half the kernel calls.
+    Checkerboard,
+    /// Like `Checkerboard`, but a pixel whose neighbours differ by more than
+    /// `threshold` palette indices (or for direct colour, by more than that
+    /// in a channel) is computed rather than averaged, to keep
+    /// filaments and the edge of the set sharp. Rows that are running late
+    /// (see `Budget`) average regardless.
+    EdgeAware { threshold: u8 },
+    /// Computes the border of a rectangle and floods it if the border is all
+    /// one value, splitting it in two otherwise. Solid regions take far fewer
+    /// kernel calls, but detail finer than the rectangles can be lost.
+    Subdivide,
+}
+
+/// The rows in each `Fill::Subdivide` band, not counting the row it shares
+/// with the band below.
+const BAND: usize = 16;
+
+/// Below this size, `Fill::Subdivide` computes a rectangle's pixels outright
+/// rather than checking its border.
+const MIN_RECT: usize = 4;
+
+/// Computes pixels of type `P` for `render_rows`, with an iteration limit.
+/// Closures taking the context, the pixel's x and y and the iteration limit
+/// compute one pixel at a time.
+pub(crate) trait Kernel<P> {
+    fn pixel(&mut self, context: &mut dyn Context, pixel_x: usize, pixel_y: usize, iter_limit: i32) -> P;
+
+    /// Computes two pixels, which a kernel can do in lockstep to keep the
+    /// pipeline busy, but otherwise one after the other.
+    #[inline(always)]
+    fn pair(&mut self, context: &mut dyn Context, pixels: [(usize, usize); 2], iter_limit: i32) -> [P; 2] {
+        pixels.map(|(pixel_x, pixel_y)| self.pixel(context, pixel_x, pixel_y, iter_limit))
+    }
+}
+
+impl<P, K: FnMut(&mut dyn Context, usize, usize, i32) -> P> Kernel<P> for K {
+    #[inline(always)]
+    fn pixel(&mut self, context: &mut dyn Context, pixel_x: usize, pixel_y: usize, iter_limit: i32) -> P {
+        self(context, pixel_x, pixel_y, iter_limit)
+    }
+}
+
+/// A kernel that computes single pixels with one closure and pairs with
+/// another, taking the context, the pixels' coordinates and the iteration
+/// limit.
+pub(crate) struct Lockstep<P, Q>(pub P, pub Q);
+
+impl<T, P, Q> Kernel<T> for Lockstep<P, Q>
+where
+    P: FnMut(&mut dyn Context, usize, usize, i32) -> T,
+    Q: FnMut(&mut dyn Context, [(usize, usize); 2], i32) -> [T; 2],
+{
+    #[inline(always)]
+    fn pixel(&mut self, context: &mut dyn Context, pixel_x: usize, pixel_y: usize, iter_limit: i32) -> T {
+        (self.0)(context, pixel_x, pixel_y, iter_limit)
+    }
+
+    #[inline(always)]
+    fn pair(&mut self, context: &mut dyn Context, pixels: [(usize, usize); 2], iter_limit: i32) -> [T; 2] {
+        (self.1)(context, pixels, iter_limit)
+    }
+}
+
+/// Computes the pixels that `pixels` yields, two at a time but for any last
+/// one, and writes them into `fb`. Returns how many there were.
+fn compute_pixels<F: PixelFormat>(context: &mut dyn Context, fb: &mut Framebuffer<F>, kernel: &mut impl Kernel<F::Pixel>, pixels: impl Iterator<Item = (usize, usize)>, iter_limit: i32) -> usize {
+    let mut count = 0;
+    let mut pending = None;
+    for (pixel_x, pixel_y) in pixels {
+        count += 1;
+        match pending.take() {
+            None => pending = Some((pixel_x, pixel_y)),
+            Some(first) => {
+                let values = kernel.pair(context, [first, (pixel_x, pixel_y)], iter_limit);
+                for ((pixel_x, pixel_y), value) in [first, (pixel_x, pixel_y)].into_iter().zip(values) {
+                    fb.row_mut(pixel_y)[pixel_x] = value;
+                }
+            }
+        }
+    }
+    if let Some((pixel_x, pixel_y)) = pending {
+        fb.row_mut(pixel_y)[pixel_x] = kernel.pixel(context, pixel_x, pixel_y, iter_limit);
+    }
+    count
+}
+
+impl Symmetry {
+    fn flip_x(self) -> bool {
+        self == Symmetry::FlipX || self == Symmetry::FourFold
+    }
+
+    fn flip_y(self) -> bool {
+        self == Symmetry::FlipY || self == Symmetry::Rotate180 || self == Symmetry::FourFold
+    }
+}
+
+/// Renders a frame into `fb`, which must be at least 2 by 2, line by line,
+/// calling `kernel` with an iteration limit for the pixels that neither
+/// `symmetry` nor `fill` give for free.
+///
+/// The pixels computed with `kernel` and those filled in from computed ones
+/// (not counting copies made for `symmetry`) are counted with
+/// `stats_count_computed` and `stats_count_interpolated`.
+///
+/// Each fill only calls `kernel` through `compute_pixels`, so that it is only
+/// inlined once per fill, except for the pixels that `Fill::EdgeAware`
+/// recomputes.
+pub(crate) fn render_rows<F: PixelFormat>(context: &mut dyn Context, fb: &mut Framebuffer<F>, frame: u32, symmetry: Symmetry, fill: Fill, kernel: impl Kernel<F::Pixel>) {
+    assert!(fb.width() >= 2 && fb.height() >= 2);
+    match fill {
+        // neighbours can't differ by more than u8::MAX
+        Fill::Checkerboard => checkerboard(context, fb, frame, symmetry, u8::MAX, kernel),
+        Fill::EdgeAware { threshold } => checkerboard(context, fb, frame, symmetry, threshold, kernel),
+        Fill::Subdivide => subdivide_bands(context, fb, frame, symmetry, kernel),
+    }
+    if symmetry.flip_y() {
+        copy_bottom_half(context, fb, symmetry);
+    }
+}
+
+/// The first of `len` columns or rows that a mirror copies, which is past the
+/// middle one if there is one.
+fn mirrored_from(len: usize) -> usize {
+    len.div_ceil(2)
+}
+
+fn mirror_row<F: PixelFormat>(fb: &mut Framebuffer<F>, pixel_y: usize) {
+    let width = fb.width();
+    let (left, right) = fb.row_mut(pixel_y).split_at_mut(mirrored_from(width));
+    for (dst, &src) in right.iter_mut().zip(left[..width/2].iter().rev()) {
+        *dst = src;
+    }
+}
+
+/// Copies the bottom half from the top half, the row past the middle included,
+/// as the beam reaches it.
+fn copy_bottom_half<F: PixelFormat>(context: &mut dyn Context, fb: &mut Framebuffer<F>, symmetry: Symmetry) {
+    let height = fb.height();
+    let first_y = mirrored_from(height);
+    let (top, mut bottom) = fb.split_at_row_mut(first_y);
+    for pixel_y in first_y..height {
+        if pixel_y > first_y {
+            context.wait_for_line(pixel_y);
+        }
+        let src = top.row(height - pixel_y - 1);
+        let dst = bottom.row_mut(pixel_y - first_y);
+        if symmetry == Symmetry::Rotate180 {
+            for (dst, &src) in dst.iter_mut().zip(src.iter().rev()) {
+                *dst = src;
+            }
+        } else {
+            dst.copy_from_slice(src);
+        }
+    }
+}
+
+/// Fills the free part of the screen in a checkerboard. The first row has no
+/// row above to average from, so it is computed in full, as is the last row
+/// when there's no symmetry to give the row below it. With a mirror, the
+/// kernel also covers one checkerboard row or column past the middle, for the
+/// pixels next to it to average from; `Rotate180` gets that row from the
+/// rotated checkerboard instead, when it lands on the row's own checkerboard,
+/// which takes the width and height to be both even or both odd. Rows in
+/// between compute every other pixel, or fewer when running late (see
+/// `Budget`).
+///
+/// The other pixels are averaged from their neighbours one row behind, unless
+/// the neighbours differ by more than `threshold`, in which case the pixel is
+/// computed after all. At the left and right edges, the neighbour inside the
+/// row stands in for the one past the edge.
+fn checkerboard<F: PixelFormat>(context: &mut dyn Context, fb: &mut Framebuffer<F>, frame: u32, symmetry: Symmetry, threshold: u8, mut kernel: impl Kernel<F::Pixel>) {
+    let (width, height) = (fb.width(), fb.height());
+    let neighbours = |fb: &Framebuffer<F>, pixel_x: usize, pixel_y| {
+        let row = fb.row(pixel_y);
+        let left = if pixel_x > 0 { pixel_x - 1 } else { pixel_x + 1 };
+        let right = if pixel_x + 1 < width { pixel_x + 1 } else { pixel_x - 1 };
+        [fb.row(pixel_y - 1)[pixel_x],
+         fb.row(pixel_y + 1)[pixel_x],
+         row[left],
+         row[right]]
+    };
+    let last_x = if symmetry.flip_x() { core::cmp::min(mirrored_from(width), width - 1) } else { width - 1 };
+    let last_y = if symmetry.flip_y() { core::cmp::min(mirrored_from(height), height - 1) } else { height - 1 };
+    let rotate_last = symmetry == Symmetry::Rotate180 && (width + height).is_multiple_of(2);
+    let budget = Budget::new(context, 0, last_y, frame);
+    // the quality the row behind was computed at
+    let mut behind = Quality::Full;
+    for pixel_y in 0..=last_y {
+        context.wait_for_line(pixel_y);
+        let (mut computed, mut interpolated) = (0, 0);
+        // the quality this row is computed at, which becomes `behind` once
+        // the row behind has been averaged
+        let mut ahead = Quality::Full;
+        if pixel_y == last_y && rotate_last {
+            // the checkerboard of an earlier row, rotated, lands on this row's
+            let (top, mut bottom) = fb.split_at_row_mut(pixel_y);
+            let src = top.row(height - pixel_y - 1);
+            let dst = bottom.row_mut(0);
+            for pixel_x in (pixel_y & 1..width).step_by(2) {
+                dst[pixel_x] = src[width - pixel_x - 1];
+            }
+        } else {
+            let quality = budget.quality(context, pixel_y);
+            let full = pixel_y == 0 || pixel_y == height - 1;
+            let (first_x, x_step) = match quality {
+                // keep the previous frame's row
+                Quality::Reuse => (width, 1),
+                _ if full => (0, 1),
+                _ => (pixel_y & 1, quality.x_step()),
+            };
+            let pixels = (first_x..=last_x).step_by(x_step).map(|pixel_x| (pixel_x, pixel_y));
+            computed += compute_pixels(context, fb, &mut kernel, pixels, quality.iter_limit());
+            if x_step == 4 {
+                let row = fb.row_mut(pixel_y);
+                for pixel_x in (first_x..=last_x).step_by(x_step).filter(|&pixel_x| pixel_x + 2 <= last_x) {
+                    row[pixel_x + 2] = row[pixel_x];
+                    interpolated += 1;
+                }
+            }
+            ahead = quality;
+        }
+        if pixel_y >= 1 {
+            let pixel_y = pixel_y - 1;
+            if pixel_y >= 1 {
+                // only worth recomputing pixels when the row was on time
+                let threshold = match behind {
+                    Quality::Full | Quality::FewerIterations => threshold,
+                    _ => u8::MAX,
+                };
+                let mut pixel_x = (pixel_y & 1) ^ 1;
+                while pixel_x <= last_x {
+                    let values = neighbours(fb, pixel_x, pixel_y);
+                    fb.row_mut(pixel_y)[pixel_x] = if F::spread(values) <= threshold {
+                        interpolated += 1;
+                        F::average(values)
+                    } else {
+                        computed += 1;
+                        kernel.pixel(context, pixel_x, pixel_y, behind.iter_limit())
+                    };
+                    pixel_x += 2;
+                }
+            }
+            if symmetry.flip_x() {
+                mirror_row(fb, pixel_y);
+            }
+        }
+        behind = ahead;
+        context.stats_count_computed(computed);
+        context.stats_count_interpolated(interpolated);
+    }
+
+    if !symmetry.flip_y() && symmetry.flip_x() {
+        mirror_row(fb, height - 1);
+    }
+}
+
+/// Fills the free part of the screen band by band, by subdivision, each band
+/// once the beam has read out all of it. Neighbouring bands share a row, so
+/// that it is only computed once, and a band is skipped altogether when
+/// `Budget` says to reuse it or the beam has already come round to it again.
+/// Otherwise the iteration limit is the only thing a late band saves on.
+fn subdivide_bands<F: PixelFormat>(context: &mut dyn Context, fb: &mut Framebuffer<F>, frame: u32, symmetry: Symmetry, mut kernel: impl Kernel<F::Pixel>) {
+    let (width, height) = (fb.width(), fb.height());
+    let last_x = if symmetry.flip_x() { mirrored_from(width) - 1 } else { width - 1 };
+    // a band needs two rows, even if the second is then copied over
+    let last_y = if symmetry.flip_y() { core::cmp::max(mirrored_from(height) - 1, 1) } else { height - 1 };
+    let budget = Budget::new(context, 0, last_y, frame);
+    let mut first_y = 0;
+    while first_y < last_y {
+        let band_y = core::cmp::min(first_y + BAND, last_y);
+        // the beam may already be past the whole band after a slow one
+        let quality = if context.lines_left(band_y) < 0 {
+            Quality::Reuse
+        } else {
+            context.wait_for_line(band_y);
+            budget.quality(context, first_y)
+        };
+        if quality != Quality::Reuse {
+            let mut rect = Rect { context, fb: &mut *fb, kernel: &mut kernel, iter_limit: quality.iter_limit() };
+            if first_y == 0 {
+                rect.compute(0, 0, last_x, 0);
+            }
+            rect.compute(0, band_y, last_x, band_y);
+            rect.compute(0, first_y + 1, 0, band_y - 1);
+            rect.compute(last_x, first_y + 1, last_x, band_y - 1);
+            rect.subdivide(0, first_y, last_x, band_y);
+        }
+        if symmetry.flip_x() {
+            for pixel_y in first_y..=band_y {
+                mirror_row(fb, pixel_y);
+            }
+        }
+        first_y = band_y;
+    }
+    if symmetry.flip_y() {
+        // the bottom half starts by overwriting the row past the middle
+        context.wait_for_line(mirrored_from(height));
+    }
+}
+
+/// Fills rectangles of `fb` for `subdivide_bands`.
+struct Rect<'a, 'b, F: PixelFormat, K> {
+    context: &'a mut dyn Context,
+    fb: &'a mut Framebuffer<'b, F>,
+    kernel: &'a mut K,
+    iter_limit: i32,
+}
+
+impl<F: PixelFormat, K: Kernel<F::Pixel>> Rect<'_, '_, F, K> {
+    /// Computes every pixel in `x0..=x1` by `y0..=y1`.
+    fn compute(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        let pixels = (y0..=y1).flat_map(|pixel_y| (x0..=x1).map(move |pixel_x| (pixel_x, pixel_y)));
+        let computed = compute_pixels(self.context, self.fb, self.kernel, pixels, self.iter_limit);
+        self.context.stats_count_computed(computed);
+    }
+
+    /// Fills the inside of the rectangle with corners (x0, y0) and (x1, y1),
+    /// whose border has already been computed.
+    fn subdivide(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        if x1 - x0 < 2 || y1 - y0 < 2 {
+            return;
+        }
+        let fb = &mut *self.fb;
+        let value = fb.row(y0)[x0];
+        let uniform = fb.row(y0)[x0..=x1].iter().chain(&fb.row(y1)[x0..=x1]).all(|&v| v == value)
+            && (y0..=y1).all(|y| fb.row(y)[x0] == value && fb.row(y)[x1] == value);
+        if uniform {
+            self.context.stats_count_interpolated((x1 - x0 - 1) * (y1 - y0 - 1));
+            for pixel_y in y0+1..y1 {
+                fb.row_mut(pixel_y)[x0+1..x1].fill(value);
+            }
+        } else if x1 - x0 <= MIN_RECT && y1 - y0 <= MIN_RECT {
+            self.compute(x0 + 1, y0 + 1, x1 - 1, y1 - 1);
+        } else if x1 - x0 >= y1 - y0 {
+            let mid_x = (x0 + x1) / 2;
+            self.compute(mid_x, y0 + 1, mid_x, y1 - 1);
+            self.subdivide(x0, y0, mid_x, y1);
+            self.subdivide(mid_x, y0, x1, y1);
+        } else {
+            let mid_y = (y0 + y1) / 2;
+            self.compute(x0 + 1, mid_y, x1 - 1, mid_y);
+            self.subdivide(x0, y0, x1, mid_y);
+            self.subdivide(x0, mid_y, x1, y1);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Orbit traps, for `Colouring::Trap`.
+//!
+//! A trap is a shape in the plane, and a point is coloured by how close its
+//! orbit comes to it rather than by when it escapes, so that the interior gets
+//! structure too.
+
+use crate::fixed::sqrt_q8;
+use crate::{cos_sin, Context, Fixed, Fx32, FRAME_MAX};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrapShape {
+    Point,
+    /// The horizontal and vertical lines through the centre.
+    Cross,
+    /// The circle about the centre with radius `size`.
+    Circle,
+}
+
+/// A trap of some shape, centred on `re + im*i`. Distances are measured in
+/// units of `size`, with the orbits that never come within `size` all getting
+/// the same palette index. The centre goes round a circle of radius `drift`
+/// over the `FRAME_MAX` frame loop.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Trap {
+    pub shape: TrapShape,
+    pub re: Fx32<16>,
+    pub im: Fx32<16>,
+    pub size: Fx32<16>,
+    pub drift: Fx32<16>,
+}
+
+impl Trap {
+    pub fn new(shape: TrapShape, re: f32, im: f32, size: f32) -> Self {
+        let fx = Fx32::from_f32;
+        Self { shape, re: fx(re), im: fx(im), size: fx(size), drift: Fx32::ZERO }
+    }
+
+    pub fn with_drift(self, drift: f32) -> Self {
+        Self { drift: Fx32::from_f32(drift), ..self }
+    }
+
+    /// Where the trap is at frame `t`.
+    pub(crate) fn at(self, t: u32) -> Self {
+        let (cos, sin) = cos_sin(Fx32::<16>::from_int(4 * (t % FRAME_MAX) as i32).div_int(FRAME_MAX as i32));
+        Self { re: self.re + self.drift * cos, im: self.im + self.drift * sin, ..self }
+    }
+
+    /// How far `z` is from the trap, in some measure that `index` turns into
+    /// a palette index and which only has to grow with the distance.
+    #[inline(always)]
+    pub(crate) fn measure<T: Fixed>(&self, context: &mut dyn Context, (a, b): (T, T)) -> T {
+        context.stats_count_adds(2);
+        let (d_a, d_b) = (a - T::from_q(self.re.0, 16), b - T::from_q(self.im.0, 16));
+        match self.shape {
+            TrapShape::Point => {
+                T::count_muls(context, 2);
+                context.stats_count_adds(1);
+                d_a*d_a + d_b*d_b
+            }
+            TrapShape::Cross => {
+                context.stats_count_cmps(3);
+                let (d_a, d_b) = (d_a.abs(), d_b.abs());
+                if d_a < d_b { d_a } else { d_b }
+            }
+            TrapShape::Circle => {
+                // ||z - centre|^2 - size^2|, which is about 2 size times the
+                // distance to the circle near it
+                T::count_muls(context, 3);
+                context.stats_count_adds(2);
+                context.stats_count_cmps(1);
+                let size = T::from_q(self.size.0, 16);
+                (d_a*d_a + d_b*d_b - size*size).abs()
+            }
+        }
+    }
+
+    /// The palette index for an orbit that came within `measure` of the trap,
+    /// from 0 on the trap to 254 at `size` away and beyond.
+    #[inline(always)]
+    pub(crate) fn index<T: Fixed>(&self, context: &mut dyn Context, measure: T) -> u8 {
+        // the distance over size, in Q8 (squared for a point)
+        let size = self.size.to_q(12);
+        context.stats_count_shrs(2);
+        context.stats_count_muls(1);
+        context.stats_count_divs(1);
+        T::count_cvts(context, 1);
+        let ratio = match self.shape {
+            TrapShape::Point => measure.to_q(20) / core::cmp::max((size * size) >> 12, 1),
+            TrapShape::Cross => measure.to_q(20) / core::cmp::max(size, 1),
+            TrapShape::Circle => measure.to_q(20) / core::cmp::max((size * size) >> 11, 1),
+        };
+        context.stats_count_cmps(2);
+        let ratio = core::cmp::min(ratio, 1 << 8);
+        let ratio = if self.shape == TrapShape::Point {
+            context.stats_count_cmps(12);
+            context.stats_count_shrs(24);
+            context.stats_count_adds(24);
+            sqrt_q8(ratio)
+        } else {
+            ratio
+        };
+        core::cmp::min(ratio, 254) as u8
+    }
+}
@@ -0,0 +1,19 @@
+//! Checks that `Animator` moves its palettes on smoothly from frame to frame,
+//! loop after loop.
+
+use demos::{Animator, Clut, Effect, Grayscale};
+
+#[test]
+fn cycles_carry_on_across_the_loop() {
+    // 255 entries, one place a frame, which doesn't divide the loop
+    let mut lut = Animator::new(&Grayscale, &[Effect::Cycle { first: 0, last: 254, period: 1 }]);
+    let mut clut = Clut::new();
+    for frame in 1..1000 {
+        let colours = lut.prepare(&mut clut);
+        for i in [0, 100, 254] {
+            let shown = ((i + frame) % 255) as u8;
+            assert_eq!(colours[i], (shown, shown, shown), "frame {}: entry {}", frame, i);
+        }
+        assert_eq!(colours[255], (255, 255, 255));
+    }
+}
@@ -0,0 +1,35 @@
+use demos::{Colours, Context, Op, Overflow};
+
+/// A context with all the time in the world, so that every row is computed
+/// in full.
+#[derive(Default)]
+pub struct Unhurried {
+    /// What was written to the colour LUT, if it's to be kept.
+    pub lut: Option<Colours>,
+    /// Every overflow reported, by pixel and iteration.
+    pub overflows: Vec<(usize, usize, i32, Op)>,
+}
+
+impl Context for Unhurried {
+    fn wait_for_line(&mut self, _pixel_y: usize) {}
+    fn lines_left(&mut self, _pixel_y: usize) -> i32 { i32::MAX }
+    fn set_lut(&mut self, i: u8, r: u8, g: u8, b: u8) {
+        if let Some(lut) = &mut self.lut {
+            lut[i as usize] = (r, g, b);
+        }
+    }
+    fn stats_count_adds(&mut self, _n: usize) {}
+    fn stats_count_cmps(&mut self, _n: usize) {}
+    fn stats_count_shrs(&mut self, _n: usize) {}
+    fn stats_count_muls(&mut self, _n: usize) {}
+    fn stats_count_mems(&mut self, _n: usize) {}
+    fn stats_count_divs(&mut self, _n: usize) {}
+    fn stats_count_fcvts(&mut self, _n: usize) {}
+    fn stats_count_fmuls(&mut self, _n: usize) {}
+    fn stats_count_computed(&mut self, _n: usize) {}
+    fn stats_count_interpolated(&mut self, _n: usize) {}
+    fn stats_count_saved_iters(&mut self, _n: usize) {}
+    fn report_overflow(&mut self, overflow: &Overflow) {
+        self.overflows.push((overflow.pixel_x, overflow.pixel_y, overflow.iter, overflow.op));
+    }
+}
@@ -0,0 +1,98 @@
+//! Checks that dithering brings the average colour of a patch closer to the
+//! colour wanted than rounding each pixel alone does.
+
+use demos::{Al44, Argb4444, Bayer, Colours, Diffusion, Levels, PixelFormat, Rgb565, L8};
+
+/// The side of each patch of one palette index, which is a whole number of
+/// Bayer tiles.
+const PATCH: usize = 8;
+/// The patches are laid out 16 to a row, one for each palette index.
+const FB_W: usize = 16 * PATCH;
+const FB_H: usize = 16 * PATCH;
+
+/// A smooth palette, as gradients are where banding shows.
+fn gradient() -> Colours {
+    core::array::from_fn(|i| (i as u8, (64 + i / 2) as u8, (255 - i) as u8))
+}
+
+/// The CLUT that `F` is shown through for `colours`, as `Animator` writes it.
+fn lut<F: PixelFormat>(colours: &Colours) -> Colours {
+    core::array::from_fn(|entry| if entry < F::LUT_ENTRIES { colours[F::lut_index(entry) as usize] } else { (0, 0, 0) })
+}
+
+/// Writes every patch in raster order, as `wait_for_line` has rows written,
+/// with `pixel` working out each one, and returns how far the average colour
+/// of a patch is from the colour wanted, on average over the patches and
+/// channels.
+fn mean_error<F: PixelFormat>(mut pixel: impl FnMut(u8, &Colours, usize, usize) -> F::Pixel) -> f64 {
+    let colours = gradient();
+    let lut = lut::<F>(&colours);
+    let index = |pixel_x: usize, pixel_y: usize| (pixel_y / PATCH * 16 + pixel_x / PATCH) as u8;
+    let mut sums = vec![[0u32; 3]; 256];
+    for pixel_y in 0..FB_H {
+        for pixel_x in 0..FB_W {
+            let (r, g, b) = F::decode(pixel(index(pixel_x, pixel_y), &colours, pixel_x, pixel_y), &lut);
+            let sum = &mut sums[index(pixel_x, pixel_y) as usize];
+            for (sum, c) in sum.iter_mut().zip([r, g, b]) {
+                *sum += c as u32;
+            }
+        }
+    }
+    let mut error = 0.0;
+    for (sum, &(r, g, b)) in sums.iter().zip(&colours) {
+        for (&sum, want) in sum.iter().zip([r, g, b]) {
+            error += (sum as f64 / (PATCH * PATCH) as f64 - want as f64).abs();
+        }
+    }
+    error / (256 * 3) as f64
+}
+
+/// Checks that each kind of dithering at least halves the error of rounding
+/// alone.
+fn assert_dithering_helps<F: Levels>() {
+    let undithered = mean_error::<F>(|index, colours, _, _| F::from_index(index, colours));
+    let dithered = [
+        ("4x4 Bayer", mean_error::<F>(|index, colours, pixel_x, pixel_y| Bayer::X4.pixel::<F>(index, colours, pixel_x, pixel_y))),
+        ("8x8 Bayer", mean_error::<F>(|index, colours, pixel_x, pixel_y| Bayer::X8.pixel::<F>(index, colours, pixel_x, pixel_y))),
+        ("diffusion", mean_error::<F>({
+            let mut diffusion = Diffusion::new();
+            move |index, colours, pixel_x, pixel_y| diffusion.pixel::<F>(index, colours, pixel_x, pixel_y)
+        })),
+    ];
+    for (name, error) in dithered {
+        assert!(error < undithered / 2.0, "{}: mean error {:.2} against {:.2} undithered", name, error, undithered);
+    }
+}
+
+#[test]
+fn dithering_reduces_the_mean_error() {
+    assert_dithering_helps::<Rgb565>();
+    assert_dithering_helps::<Argb4444>();
+    assert_dithering_helps::<Al44>();
+}
+
+#[test]
+fn l8_is_left_alone() {
+    let colours = gradient();
+    let mut diffusion = Diffusion::new();
+    for index in 0..=255 {
+        let (pixel_x, pixel_y) = (index as usize % 13, index as usize / 13);
+        assert_eq!(Bayer::X8.pixel::<L8>(index, &colours, pixel_x, pixel_y), index);
+        assert_eq!(diffusion.pixel::<L8>(index, &colours, pixel_x, pixel_y), index);
+    }
+}
+
+#[test]
+fn bayer_matrices_hold_each_entry_once() {
+    for (bayer, side) in [(Bayer::X4, 4), (Bayer::X8, 8)] {
+        let mut seen = vec![false; side * side];
+        for pixel_y in 0..side {
+            for pixel_x in 0..side {
+                let entry = bayer.entry(pixel_x, pixel_y) as usize;
+                assert!(!seen[entry], "{:?}: entry {} twice", bayer, entry);
+                seen[entry] = true;
+                assert_eq!(bayer.entry(pixel_x + side, pixel_y + 2*side), entry as u32);
+            }
+        }
+    }
+}
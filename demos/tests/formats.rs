@@ -0,0 +1,77 @@
+//! Checks that each pixel format shows the colours that L8 does, to within
+//! its precision.
+
+mod common;
+
+use common::Unhurried;
+use demos::{Al44, Argb4444, Argb8888, Clut, Colours, Demo, Fill, Framebuffer, Fx32, Julia, PixelFormat, Rgb565, Styled, TrueColour, L8};
+
+const FB_W: usize = 480;
+const FB_H: usize = 272;
+
+/// Renders a few frames of Julia in format `F` and hands `check` the colour
+/// shown for each computed pixel, with the palette index L8 has there.
+/// `Fill::Checkerboard` computes the same pixels in every format, but
+/// averages the others in palette indices for L8 and in colour otherwise, so
+/// those aren't compared.
+fn compare<F: PixelFormat>(mut check: impl FnMut(u8, &Colours, (u8, u8, u8))) {
+    let julia = || Julia::<Fx32<10>>::new().with_fill(Fill::Checkerboard);
+    let (mut l8, mut other) = (julia(), julia());
+    let keeping_lut = || Unhurried { lut: Some([(0, 0, 0); 256]), ..Default::default() };
+    let (mut l8_context, mut context) = (keeping_lut(), keeping_lut());
+    let (mut l8_clut, mut clut) = (Clut::new(), Clut::new());
+    let mut expected = vec![0; FB_W*FB_H];
+    let mut actual = vec![F::Pixel::default(); FB_W*FB_H];
+    for frame in 1..=30 {
+        Demo::<L8>::render(&mut l8, &mut l8_context, &mut l8_clut, &mut Framebuffer::new(&mut expected, FB_W, FB_H, FB_W));
+        Demo::<F>::render(&mut other, &mut context, &mut clut, &mut Framebuffer::new(&mut actual, FB_W, FB_H, FB_W));
+        // the LUT for a frame is written once it's rendered
+        Demo::<L8>::pre_render(&mut l8, &mut l8_context, &mut l8_clut);
+        Demo::<F>::pre_render(&mut other, &mut context, &mut clut);
+        if frame % 10 != 0 {
+            continue;
+        }
+        for (i, (&index, &pixel)) in expected.iter().zip(&actual).enumerate() {
+            if (i % FB_W + i / FB_W).is_multiple_of(2) {
+                check(index, l8_context.lut.as_ref().unwrap(), F::decode(pixel, context.lut.as_ref().unwrap()));
+            }
+        }
+    }
+}
+
+/// Checks that a direct-colour format shows each colour to within the bits
+/// it keeps of each channel.
+fn assert_close<F: TrueColour>() {
+    let mut worst = [0; 3];
+    compare::<F>(|index, l8_lut, (r, g, b)| {
+        let want = l8_lut[index as usize];
+        for (c, (got, want)) in [(r, want.0), (g, want.1), (b, want.2)].into_iter().enumerate() {
+            worst[c] = worst[c].max(got.abs_diff(want));
+        }
+    });
+    for (c, worst) in worst.into_iter().enumerate() {
+        assert!(worst < 1 << (8 - F::BITS[c]), "channel {}: off by {}", c, worst);
+    }
+}
+
+#[test]
+fn al44_shows_the_middle_of_each_run_of_16() {
+    compare::<Al44>(|index, l8_lut, colour| {
+        assert_eq!(colour, l8_lut[(index & 0xF0 | 8) as usize], "index {}", index);
+    });
+}
+
+#[test]
+fn direct_colour_matches_l8() {
+    assert_close::<Rgb565>();
+    assert_close::<Argb4444>();
+    assert_close::<Argb8888>();
+}
+
+#[test]
+fn lut_indices_are_the_middle_of_each_run() {
+    assert_eq!((0..256).map(L8::lut_index).collect::<Vec<_>>(), (0..=255).collect::<Vec<_>>());
+    assert_eq!((0..16).map(Al44::lut_index).collect::<Vec<_>>(), (0..16).map(|entry| entry * 16 + 8).collect::<Vec<_>>());
+    // no CLUT to share out, but asking mustn't divide by its size
+    assert_eq!(Rgb565::lut_index(3), 3);
+}
@@ -0,0 +1,47 @@
+//! Checks that `Framebuffer` rows respect the stride and that split borrows
+//! land on the right rows.
+
+use demos::{Framebuffer, L8};
+
+#[test]
+fn rows_skip_the_padding() {
+    let mut pixels = [0xEE; 4*3];
+    let mut fb = Framebuffer::<L8>::new(&mut pixels, 3, 3, 4);
+    fb.fill(1);
+    fb.row_mut(1).copy_from_slice(&[2, 3, 4]);
+    assert_eq!(fb.row(1), [2, 3, 4]);
+    assert_eq!(fb.rows().count(), 3);
+    assert_eq!(pixels, [1, 1, 1, 0xEE, 2, 3, 4, 0xEE, 1, 1, 1, 0xEE]);
+}
+
+#[test]
+fn a_short_last_row_is_enough() {
+    let mut pixels = [0; 4*2 + 3];
+    let fb = Framebuffer::<L8>::new(&mut pixels, 3, 3, 4);
+    assert_eq!(fb.row(2).len(), 3);
+}
+
+#[test]
+#[should_panic]
+fn too_few_pixels_panics() {
+    Framebuffer::<L8>::new(&mut [0; 4*2 + 2], 3, 3, 4);
+}
+
+#[test]
+fn split_borrows_are_disjoint() {
+    let mut pixels: Vec<u8> = (0..4*5).collect();
+    let mut fb = Framebuffer::<L8>::new(&mut pixels, 4, 5, 4);
+    let (top, mut bottom) = fb.split_at_row_mut(2);
+    assert_eq!((top.height(), bottom.height()), (2, 3));
+    bottom.row_mut(0).copy_from_slice(top.row(1));
+    assert_eq!(fb.row(2), [4, 5, 6, 7]);
+
+    let mut lines = fb.lines_mut(3..5);
+    assert_eq!(lines.height(), 2);
+    lines.fill(9);
+    assert_eq!(fb.row(2), [4, 5, 6, 7]);
+    assert_eq!(fb.row(4), [9, 9, 9, 9]);
+
+    let (top, bottom) = fb.split_at_row_mut(5);
+    assert_eq!((top.height(), bottom.height()), (5, 0));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::Unhurried;
+use demos::{Clut, Colouring, Demo, Fill, Framebuffer, Fx32, Fx64, Interior, Julia, Styled, Trap, TrapShape};
+
+const FB_W: usize = 480;
+const FB_H: usize = 272;
+
+/// Renders frames of `lockstep` and `single` side by side, each into its own
+/// framebuffer, checking that every pixel of every tenth frame comes out the
+/// same.
+fn assert_same_frames(name: &str, mut lockstep: impl Demo, mut single: impl Demo) {
+    let (mut expected, mut actual) = (vec![0; FB_W*FB_H], vec![0; FB_W*FB_H]);
+    let (mut lockstep_clut, mut single_clut) = (Clut::new(), Clut::new());
+    for frame in 1..=30 {
+        lockstep.render(&mut Unhurried::default(), &mut lockstep_clut, &mut Framebuffer::new(&mut expected, FB_W, FB_H, FB_W));
+        single.render(&mut Unhurried::default(), &mut single_clut, &mut Framebuffer::new(&mut actual, FB_W, FB_H, FB_W));
+        if frame % 10 != 0 {
+            continue;
+        }
+        for (i, (&want, &got)) in expected.iter().zip(&actual).enumerate() {
+            assert_eq!(got, want, "{}, frame {}: pixel ({}, {})", name, frame, i % FB_W, i / FB_W);
+        }
+    }
+}
+
+#[test]
+fn lockstep_matches_single_pixels() {
+    let trap = Trap::new(TrapShape::Circle, 0.1, 0.2, 0.3).with_drift(0.2);
+    let cases = [
+        (Colouring::Lerp, Fill::Checkerboard, Interior::Flat),
+        (Colouring::Lerp, Fill::Subdivide, Interior::Flat),
+        (Colouring::Smooth, Fill::EdgeAware { threshold: 16 }, Interior::Period),
+        (Colouring::Smooth, Fill::Subdivide, Interior::Period),
+        (Colouring::Distance, Fill::Checkerboard, Interior::Flat),
+        (Colouring::Trap(trap), Fill::Checkerboard, Interior::Flat),
+        (Colouring::Decomposition { sectors: 6 }, Fill::EdgeAware { threshold: 64 }, Interior::Flat),
+    ];
+    for (colouring, fill, interior) in cases {
+        let name = format!("{:?}, {:?}, {:?}", colouring, fill, interior);
+        let julia = || Julia::<Fx32<10>>::new().with_colouring(colouring).with_fill(fill).with_interior(interior);
+        assert_same_frames(&name, julia(), julia().with_lockstep(false));
+    }
+    let julia = || Julia::<Fx64<24>>::new().with_colouring(Colouring::Smooth);
+    assert_same_frames("Fx64<24>", julia(), julia().with_lockstep(false));
+}
+
+/// With too few integer bits for the orbits, the lockstep kernel reports the
+/// same overflows at the same pixels and iterations as the single-pixel one.
+#[cfg(feature = "checked")]
+#[test]
+fn lockstep_reports_overflows_at_the_right_pixels() {
+    let julia = || Julia::<Fx32<28>>::new();
+    let reports = [julia(), julia().with_lockstep(false)].map(|mut julia| {
+        let mut context = Unhurried::default();
+        let mut pixels = vec![0; FB_W*FB_H];
+        julia.render(&mut context, &mut Clut::new(), &mut Framebuffer::<demos::L8>::new(&mut pixels, FB_W, FB_H, FB_W));
+        context.overflows.sort_by_key(|&(pixel_x, pixel_y, iter, _)| (pixel_y, pixel_x, iter));
+        context.overflows
+    });
+    assert!(!reports[1].is_empty());
+    assert_eq!(reports[0], reports[1]);
+}
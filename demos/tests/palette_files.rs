@@ -0,0 +1,58 @@
+//! Runs the palette file parser used by `build.rs` over the shipped palettes
+//! and over malformed samples in `tests/palettes/`.
+
+#[path = "../build/palette_file.rs"]
+mod palette_file;
+
+use palette_file::{parse, Error, Lut};
+
+fn parse_file(path: &str) -> Result<Lut, Error> {
+    let text = std::fs::read_to_string(path).unwrap();
+    parse(path.rsplit('.').next().unwrap(), &text)
+}
+
+fn error(line: Option<usize>, message: &str) -> Error {
+    Error { line, message: message.to_string() }
+}
+
+#[test]
+fn shipped_palettes_parse() {
+    for entry in std::fs::read_dir("palettes").unwrap() {
+        let path = entry.unwrap().path();
+        parse_file(path.to_str().unwrap()).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    }
+}
+
+#[test]
+fn gpl_is_stretched_over_the_lut() {
+    let lut = parse_file("palettes/sunset.gpl").unwrap();
+    assert_eq!(lut[0], (8, 4, 32));
+    assert_eq!(lut[51], (48, 16, 96));
+    assert_eq!(lut[255], (255, 248, 224));
+    // halfway between the first two colours
+    assert_eq!(lut[25], (27, 9, 63));
+}
+
+#[test]
+fn map_with_256_colours_is_used_as_is() {
+    let text = std::fs::read_to_string("palettes/volcano.map").unwrap();
+    let lut = parse("map", &text).unwrap();
+    for (line, entry) in text.lines().zip(lut) {
+        let rgb: Vec<u8> = line.split_whitespace().take(3).map(|x| x.parse().unwrap()).collect();
+        assert_eq!((rgb[0], rgb[1], rgb[2]), entry);
+    }
+}
+
+#[test]
+fn malformed_files_are_rejected() {
+    assert_eq!(parse_file("tests/palettes/bad_header.gpl"), Err(error(Some(1), "expected a \"GIMP Palette\" header")));
+    assert_eq!(parse_file("tests/palettes/missing_component.gpl"), Err(error(Some(5), "missing blue component")));
+    assert_eq!(parse_file("tests/palettes/out_of_range.map"), Err(error(Some(2), "green component 300 is over 255")));
+    assert_eq!(parse_file("tests/palettes/not_a_number.map"), Err(error(Some(2), "green component \"x4\" is not a number")));
+    assert_eq!(parse_file("tests/palettes/one_colour.map"), Err(error(None, "expected 2 to 256 colours, found 1")));
+}
+
+#[test]
+fn unknown_format_is_rejected() {
+    assert_eq!(parse("act", ""), Err(error(None, "unknown palette format \"act\", expected gpl or map")));
+}
@@ -0,0 +1,28 @@
+//! Checks that orbits are only stopped early as periodic when they really
+//! repeat.
+
+mod common;
+
+use common::Unhurried;
+use demos::{Clut, Demo, EscapeTime, Framebuffer, Interior, Phoenix, Plane, Styled, L8};
+
+/// Renders z = `re` for c = `c_a`, in a view too small to tell its pixels
+/// apart.
+fn phoenix_pixels(re: f32, c_a: f32, interior: Interior) -> [u8; 4] {
+    let mut phoenix = EscapeTime::<Phoenix>::new(Phoenix::new(), Plane::dynamic(c_a, 0.0), re, 0.0, 0.0).with_interior(interior);
+    let mut pixels = [0; 4];
+    phoenix.render(&mut Unhurried::default(), &mut Clut::new(), &mut Framebuffer::<L8>::new(&mut pixels, 2, 2, 2));
+    pixels
+}
+
+/// z = -1.5 is a fixed point of z^2 - 3.75, so the first Phoenix step lands
+/// back on it, but with z_prev gone from 0 to -1.5, so the next one doesn't,
+/// and the orbit escapes on the third.
+#[test]
+fn phoenix_orbits_repeat_only_with_z_prev() {
+    for interior in [Interior::Flat, Interior::Period] {
+        for index in phoenix_pixels(-1.5, -3.75, interior) {
+            assert!(index as i32 <= 3 * 255 / 36, "{:?}: index {}", interior, index);
+        }
+    }
+}
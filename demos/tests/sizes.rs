@@ -0,0 +1,59 @@
+//! Runs every demo at a range of framebuffer sizes, odd ones included.
+
+mod common;
+
+use common::Unhurried;
+use demos::{Clut, Demo, Framebuffer, PixelFormat, Registry, Rgb565, L8};
+
+/// Width, height and stride.
+const SIZES: [(usize, usize, usize); 8] = [
+    (480, 272, 480),
+    (240, 136, 240),
+    (800, 480, 800),
+    (101, 57, 101),
+    (64, 33, 67),
+    (33, 64, 40),
+    (5, 3, 5),
+    (2, 2, 2),
+];
+
+/// Renders each demo into two framebuffers that start out different, so that
+/// any pixel left unwritten, or written from a pixel that hasn't been yet,
+/// shows up as a difference between them. The padding past each row has to
+/// stay as it was.
+fn writes_every_pixel<F: PixelFormat>() where F::Pixel: From<u8> {
+    let blank = [F::Pixel::from(0x00), F::Pixel::from(0xFF)];
+    for (width, height, stride) in SIZES {
+        let len = (height - 1) * stride + width;
+        for index in 0..Registry::len() {
+            let mut demos = [Registry::new(), Registry::new()];
+            let mut pixels = blank.map(|value| vec![value; len]);
+            for (demo, pixels) in demos.iter_mut().zip(&mut pixels) {
+                let mut clut = Clut::new();
+                demo.select::<F>(index, &mut Unhurried::default(), &mut clut);
+                for _ in 0..2 {
+                    demo.render(&mut Unhurried::default(), &mut clut, &mut Framebuffer::<F>::new(pixels, width, height, stride));
+                }
+            }
+            let name = format!("{} at {}x{}", demos[0].name(), width, height);
+            for (i, (&a, &b)) in pixels[0].iter().zip(&pixels[1]).enumerate() {
+                let (pixel_x, pixel_y) = (i % stride, i / stride);
+                if pixel_x < width {
+                    assert_eq!(a, b, "{}: pixel ({}, {})", name, pixel_x, pixel_y);
+                } else {
+                    assert_eq!([a, b], blank, "{}: padding ({}, {})", name, pixel_x, pixel_y);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn every_demo_writes_every_pixel() {
+    writes_every_pixel::<L8>();
+}
+
+#[test]
+fn every_demo_writes_every_pixel_in_direct_colour() {
+    writes_every_pixel::<Rgb565>();
+}
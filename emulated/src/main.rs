@@ -1,14 +1,28 @@
+#![allow(clippy::identity_op)]
+
 use sdl2::rect::Point;
 use sdl2::pixels::Color;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use std::time::Duration;
 
-const FB_W: usize = demos::FB_W;
-const FB_H: usize = demos::FB_H;
+use demos::PixelFormat;
+
+/// The panel's resolution, as the firmware drives it.
+const FB_W: usize = 480;
+const FB_H: usize = 272;
+
+/// Lines per refresh, including blanking, as set up by the firmware.
+const TOTAL_LINES: usize = 288;
+/// Cost units per line period, taking one unit as roughly one cycle: 216 MHz
+/// core clock / (5.4 MHz pixel clock / 525 pixels per line).
+const COST_PER_LINE: usize = 21000;
 
 struct ContextS<'a> {
     lut: &'a mut [(u8, u8, u8)],
+    /// Whether the demo is rendering into the framebuffer on screen, and so
+    /// has to keep ahead of the beam.
+    racing: bool,
     adds: usize,
     cmps: usize,
     shrs: usize,
@@ -17,10 +31,55 @@ struct ContextS<'a> {
     divs: usize,
     fcvts: usize,
     fmuls: usize,
+    computed: usize,
+    interpolated: usize,
+    saved_iters: usize,
+    overflows: usize,
+    /// The cost at which render started, and how far into the active area
+    /// the beam was by then.
+    start: usize,
+    offset: usize,
+    /// Cost units spent waiting for the beam.
+    waited: usize,
+    misses: usize,
+}
+
+impl<'a> ContextS<'a> {
+    fn cost(&self) -> usize {
+        1*self.adds +
+        1*self.cmps +
+        1*self.shrs +
+        1*self.muls +
+        2*self.mems +
+        10*self.divs +
+        1*self.fcvts +
+        3*self.fmuls
+    }
+
+    /// Where the beam would be by now, in cost units since it entered the
+    /// active area.
+    fn beam(&self) -> usize {
+        self.offset + self.cost() + self.waited - self.start
+    }
 }
 
 impl<'a> demos::Context for ContextS<'a> {
-    fn wait_for_line(&mut self, _pixel_y: usize) {
+    fn wait_for_line(&mut self, pixel_y: usize) {
+        if !self.racing {
+            return;
+        }
+        let passed = (pixel_y + 1) * COST_PER_LINE;
+        if self.beam() < passed {
+            self.waited += passed - self.beam();
+        } else if self.lines_left(pixel_y) < 0 {
+            self.misses += 1;
+        }
+    }
+    fn lines_left(&mut self, pixel_y: usize) -> i32 {
+        if !self.racing {
+            return i32::MAX;
+        }
+        (TOTAL_LINES + pixel_y) as i32 - (self.beam() / COST_PER_LINE) as i32
     }
     fn set_lut(&mut self, i: u8, r: u8, g: u8, b: u8) {
         self.lut[i as usize] = (r, g, b);
@@ -33,78 +92,218 @@ impl<'a> demos::Context for ContextS<'a> {
     fn stats_count_divs(&mut self, n: usize) { self.divs += n; }
     fn stats_count_fcvts(&mut self, n: usize) { self.fcvts += n; }
     fn stats_count_fmuls(&mut self, n: usize) { self.fmuls += n; }
+    fn stats_count_computed(&mut self, n: usize) { self.computed += n; }
+    fn stats_count_interpolated(&mut self, n: usize) { self.interpolated += n; }
+    fn stats_count_saved_iters(&mut self, n: usize) { self.saved_iters += n; }
+    fn report_overflow(&mut self, overflow: &demos::Overflow) {
+        if self.overflows == 0 {
+            println!("overflow: {:?} at pixel ({}, {}), iteration {}",
+                     overflow.op, overflow.pixel_x, overflow.pixel_y, overflow.iter);
+        }
+        self.overflows += 1;
+    }
+}
+
+/// One demo with its own framebuffer in pixel format `F` and colour LUT,
+/// drawn into its own part of the window.
+struct Pane<F: PixelFormat> {
+    state: demos::Registry,
+    /// The framebuffer on screen.
+    fb: Vec<F::Pixel>,
+    /// When double-buffered, the framebuffer that the demo renders into,
+    /// which is swapped with `fb` at the start of the next vertical blanking
+    /// period after the render, as the firmware does.
+    back: Option<Vec<F::Pixel>>,
+    lut: [(u8, u8, u8); 256],
+    clut: demos::Clut,
+    /// How far into the active area the beam will be when the next render
+    /// starts, which is only nonzero if the last one ran into the next
+    /// refresh, leaving the line interrupt pending.
+    offset: usize,
+}
+
+impl<F: PixelFormat> Pane<F> {
+    fn new(name: &str, double_buffered: bool) -> Self {
+        let back = double_buffered.then(|| vec![F::Pixel::default(); FB_W*FB_H]);
+        let mut pane = Pane { state: demos::Registry::new(), fb: vec![F::Pixel::default(); FB_W*FB_H], back, lut: [(0u8, 0u8, 0u8); 256], clut: demos::Clut::new(), offset: 0 };
+        let index = demos::Registry::find(name).unwrap_or_else(|| {
+            panic!("unknown demo {:?}, expected one of {:?}", name, demos::Registry::names().collect::<Vec<_>>())
+        });
+        pane.frame(Some(index));
+        pane
+    }
+
+    fn frame(&mut self, select: Option<usize>) {
+        let mut context = ContextS {
+            lut: &mut self.lut,
+            racing: self.back.is_none(),
+            adds: 0,
+            cmps: 0,
+            shrs: 0,
+            muls: 0,
+            mems: 0,
+            divs: 0,
+            fcvts: 0,
+            fmuls: 0,
+            computed: 0,
+            interpolated: 0,
+            saved_iters: 0,
+            overflows: 0,
+            start: 0,
+            offset: self.offset,
+            waited: 0,
+            misses: 0,
+        };
+        use demos::Demo;
+        if let Some(index) = select {
+            self.state.select::<F>(index, &mut context, &mut self.clut);
+            println!("demo: {}", self.state.name());
+        }
+        Demo::<F>::pre_render(&mut self.state, &mut context, &mut self.clut);
+        context.start = context.cost();
+        let pixels = match &mut self.back {
+            Some(back) => back,
+            None => &mut self.fb,
+        };
+        self.state.render(&mut context, &mut self.clut, &mut demos::Framebuffer::<F>::new(pixels, FB_W, FB_H, FB_W));
+        let refresh = TOTAL_LINES * COST_PER_LINE;
+        // the misses, or for a double-buffered frame the refreshes it stays on
+        // screen for: the next render starts once it's swapped in, and it can
+        // only be swapped in at the first vertical blanking after the render
+        let (last_column, last) = match &mut self.back {
+            None => {
+                self.offset = context.beam().saturating_sub(refresh) % refresh;
+                ('!', context.misses)
+            },
+            Some(back) => {
+                std::mem::swap(&mut self.fb, back);
+                ('@', (context.cost() - context.start).div_ceil(refresh).max(1))
+            },
+        };
+        let scale = 100000;
+        println!("{:4} +{:4} >{:4} >>{:4} *{:4} []{:4} /{:4} fi{:4} f*{:4} ={:6} ~{:6} -{:7} {}{:4}",
+                 context.cost() / scale,
+
+                 (1*context.adds)/scale,
+                 (1*context.cmps)/scale,
+                 (1*context.shrs)/scale,
+                 (1*context.muls)/scale,
+                 (2*context.mems)/scale,
+                 (10*context.divs)/scale,
+                 (1*context.fcvts)/scale,
+                 (3*context.fmuls)/scale,
+                 context.computed,
+                 context.interpolated,
+                 context.saved_iters,
+                 last_column,
+                 last);
+        if context.overflows > 1 {
+            println!("overflow: {} overflows this frame", context.overflows);
+        }
+    }
+
+    fn draw(&self, canvas: &mut sdl2::render::WindowCanvas, x_offset: usize) {
+        for (y, row) in self.fb.chunks(FB_W).enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                let (r, g, b) = F::decode(pixel, &self.lut);
+                canvas.set_draw_color(Color::RGB(r, g, b));
+                canvas.draw_point(Point::new((x_offset + x) as i32, y as i32)).unwrap();
+            }
+        }
+    }
 }
 
+/// Usage: `emulated [DEMO]`, or `emulated --compare DEMO DEMO` to run two
+/// demos side by side (e.g. `--compare julia julia-q16` to compare
+/// precisions), either with `--double-buffered` first to render off screen
+/// and/or `--format FORMAT` to render in a pixel format other than L8.
+/// The arrow keys switch every pane to the next/previous demo.
 pub fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut double_buffered = false;
+    let mut format = String::from("l8");
+    loop {
+        match args.first().map(String::as_str) {
+            Some("--double-buffered") => {
+                double_buffered = true;
+                args.remove(0);
+            },
+            Some("--format") if args.len() > 1 => {
+                format = args.remove(1);
+                args.remove(0);
+            },
+            _ => break,
+        }
+    }
+    let names: Vec<&str> = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => vec![demos::Registry::names().next().unwrap()],
+        ["--compare", a, b] => vec![a, b],
+        [name] if !name.starts_with('-') => vec![name],
+        _ => panic!("usage: emulated [--double-buffered] [--format FORMAT] [DEMO] | emulated [--double-buffered] [--format FORMAT] --compare DEMO DEMO"),
+    };
+    match format.as_str() {
+        "l8" => run::<demos::L8>(&names, double_buffered),
+        "al44" => run::<demos::Al44>(&names, double_buffered),
+        "rgb565" => run::<demos::Rgb565>(&names, double_buffered),
+        "argb4444" => run::<demos::Argb4444>(&names, double_buffered),
+        "argb8888" => run::<demos::Argb8888>(&names, double_buffered),
+        _ => panic!("unknown format {:?}, expected one of l8, al44, rgb565, argb4444 or argb8888", format),
+    }
+}
+
+/// Runs demos `names` side by side in pixel format `F` until the window is
+/// closed.
+fn run<F: PixelFormat>(names: &[&str], double_buffered: bool) {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
     let window = video_subsystem
-        .window("stm32f7508-dk", FB_W.try_into().unwrap(), FB_H.try_into().unwrap())
+        .window("stm32f7508-dk", (names.len() * FB_W).try_into().unwrap(), FB_H.try_into().unwrap())
         .position_centered()
         .build()
         .unwrap();
 
     let mut canvas = window.into_canvas().build().unwrap();
 
-    let mut lut = [(0u8, 0u8, 0u8); 256];
-
-    let mut state = demos::Julia::new();
+    let mut panes: Vec<Pane<F>> = names.iter().map(|name| Pane::new(name, double_buffered)).collect();
+    let set_title = |canvas: &mut sdl2::render::WindowCanvas, panes: &[Pane<F>]| {
+        let names: Vec<&str> = panes.iter().map(|pane| pane.state.name()).collect();
+        canvas.window_mut().set_title(&format!("stm32f7508-dk: {}", names.join(" vs "))).unwrap();
+    };
+    set_title(&mut canvas, &panes);
 
     let mut event_pump = sdl_context.event_pump().unwrap();
     'running: loop {
+        let mut demo_step = 0;
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit {..} |
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'running
                 },
+                Event::KeyDown { keycode: Some(Keycode::Right), .. } |
+                Event::KeyDown { keycode: Some(Keycode::N), .. } => {
+                    demo_step = 1;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Left), .. } |
+                Event::KeyDown { keycode: Some(Keycode::P), .. } => {
+                    demo_step = -1;
+                },
                 _ => {}
             }
         }
 
-        {
-            let mut context = ContextS {
-                lut: &mut lut,
-                adds: 0,
-                cmps: 0,
-                shrs: 0,
-                muls: 0,
-                mems: 0,
-                divs: 0,
-                fcvts: 0,
-                fmuls: 0,
+        for (i, pane) in panes.iter_mut().enumerate() {
+            let len = demos::Registry::len();
+            let select = match demo_step {
+                0 => None,
+                _ => Some((pane.state.index() + len).wrapping_add_signed(demo_step) % len),
             };
-            use demos::Demo;
-            state.pre_render(&mut context);
-            state.render(&mut context);
-            let scale = 100000;
-            println!("{:4} +{:4} >{:4} >>{:4} *{:4} []{:4} /{:4} fi{:4} f*{:4}",
-                     (1*context.adds +
-                     1*context.cmps +
-                     1*context.shrs +
-                     1*context.muls +
-                     2*context.mems +
-                     10*context.divs +
-                     1*context.fcvts +
-                     3*context.fmuls) / scale,
-
-                     (1*context.adds)/scale,
-                     (1*context.cmps)/scale,
-                     (1*context.shrs)/scale,
-                     (1*context.muls)/scale,
-                     (2*context.mems)/scale,
-                     (10*context.divs)/scale,
-                     (1*context.fcvts)/scale,
-                     (3*context.fmuls)/scale);
+            pane.frame(select);
+            pane.draw(&mut canvas, i * FB_W);
         }
-
-        for y in 0..FB_H {
-            for x in 0..FB_W {
-                let (r, g, b) = lut[demos::fb()[y * FB_W + x] as usize];
-                canvas.set_draw_color(Color::RGB(r, g, b));
-                canvas.draw_point(Point::new(x as i32, y as i32)).unwrap();
-            }
+        if demo_step != 0 {
+            set_title(&mut canvas, &panes);
         }
 
         canvas.present();
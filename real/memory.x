@@ -2,10 +2,12 @@ MEMORY
 {
   /* NOTE 1 K = 1 KiBi = 1024 bytes */
   FLASH : ORIGIN = 0x00200000, LENGTH = 64K
-  STACK : ORIGIN = 0x20000000, LENGTH = 1K
+  /* The deepest path measured is the LTDC interrupt rendering a Julia set in
+     ARGB4444, at about 1.2K with main's frame and the exception frame, so
+     leave plenty of room above it. */
+  STACK : ORIGIN = 0x20000000, LENGTH = 4K
   PRIORITY : ORIGIN = 0x20000000 + LENGTH(STACK), LENGTH = 0
-  FB : ORIGIN = 0x20000000 + LENGTH(STACK) + LENGTH(PRIORITY), LENGTH = 480*272
-  RAM : ORIGIN = 0x20000000 + LENGTH(STACK) + LENGTH(PRIORITY) + LENGTH(FB), LENGTH = 320K - LENGTH(STACK) - LENGTH(PRIORITY) - LENGTH(FB)
+  RAM : ORIGIN = 0x20000000 + LENGTH(STACK) + LENGTH(PRIORITY), LENGTH = 320K - LENGTH(STACK) - LENGTH(PRIORITY)
 }
 
 /* This is where the call stack will be allocated. */
@@ -31,9 +33,13 @@ SECTIONS {
        *(.priority);
        . = ALIGN(16);
      } > PRIORITY
+   } INSERT AFTER .bss;
 
+/* The framebuffer goes at the start of RAM, taking however much the firmware
+   declares with `take_framebuffer!` for the panel's resolution. */
+SECTIONS {
      .fb (NOLOAD) : ALIGN(4) {
        *(.fb);
        . = ALIGN(16);
-     } > FB
-   } INSERT AFTER .bss;
+     } > RAM
+   } INSERT BEFORE .data;
@@ -6,13 +6,19 @@ use panic_halt as _;
 
 use core::cell::RefCell;
 use core::convert::TryInto;
+use core::sync::atomic::{AtomicU32, Ordering};
 
 use cortex_m::interrupt::Mutex;
 use cortex_m_rt::entry;
-use stm32f7::stm32f750::{interrupt, Interrupt, LTDC, NVIC};
+use stm32f7::stm32f750::{interrupt, Interrupt, GPIOI, LTDC, NVIC};
+use stm32f7::stm32f750::ltdc::layer::pfcr::PF_A;
 
 static GLTDC: Mutex<RefCell<Option<LTDC>>> = Mutex::new(RefCell::new(None));
-static GSTATE: Mutex<RefCell<Option<demos::Julia>>> = Mutex::new(RefCell::new(None));
+static GSTATE: Mutex<RefCell<Option<demos::Registry>>> = Mutex::new(RefCell::new(None));
+/// Too big to build on the stack, so initialised in place.
+static GCLUT: Mutex<RefCell<demos::Clut>> = Mutex::new(RefCell::new(demos::Clut::new()));
+static GFB: Mutex<RefCell<Option<Buffers>>> = Mutex::new(RefCell::new(None));
+static GBUTTON: Mutex<RefCell<Option<UserButton>>> = Mutex::new(RefCell::new(None));
 
 struct LTDCInfo {
     hsync: u16,
@@ -39,8 +45,91 @@ const LTDC_INFO: LTDCInfo = LTDCInfo {
 const FB_W: usize = LTDC_INFO.aw as usize;
 const FB_H: usize = LTDC_INFO.ah as usize;
 
-const _: () = assert!(FB_W == demos::FB_W);
-const _: () = assert!(FB_H == demos::FB_H);
+const DOUBLE_BUFFERED: bool = cfg!(feature = "double-buffered");
+
+#[cfg(any(all(feature = "al44", feature = "rgb565"), all(feature = "al44", feature = "argb4444"), all(feature = "rgb565", feature = "argb4444")))]
+compile_error!("the al44, rgb565 and argb4444 features each choose the pixel format, so turn on one at most");
+#[cfg(all(feature = "double-buffered", any(feature = "rgb565", feature = "argb4444")))]
+compile_error!("two 16-bit framebuffers don't fit in SRAM, so rgb565 and argb4444 can't be double-buffered");
+
+/// The pixel format of the framebuffers, L8 unless a format feature is on.
+/// Only one is defined even if more are on, so that the `compile_error!`
+/// above is all that's reported.
+#[cfg(not(any(feature = "al44", feature = "rgb565", feature = "argb4444")))]
+type Format = demos::L8;
+#[cfg(feature = "al44")]
+type Format = demos::Al44;
+#[cfg(all(feature = "rgb565", not(feature = "al44")))]
+type Format = demos::Rgb565;
+#[cfg(all(feature = "argb4444", not(any(feature = "al44", feature = "rgb565"))))]
+type Format = demos::Argb4444;
+
+/// The LTDC's code for each pixel format.
+trait LtdcFormat: demos::PixelFormat {
+    const PF: PF_A;
+}
+
+impl LtdcFormat for demos::L8 {
+    const PF: PF_A = PF_A::L8;
+}
+
+impl LtdcFormat for demos::Al44 {
+    const PF: PF_A = PF_A::AL44;
+}
+
+impl LtdcFormat for demos::Rgb565 {
+    const PF: PF_A = PF_A::RGB565;
+}
+
+impl LtdcFormat for demos::Argb4444 {
+    const PF: PF_A = PF_A::ARGB4444;
+}
+
+/// The framebuffer on screen, which demos race the beam through.
+#[cfg(not(feature = "double-buffered"))]
+struct Buffers {
+    front: demos::Framebuffer<'static, Format>,
+}
+
+/// The framebuffer on screen and the one being rendered, which are swapped
+/// during vertical blanking once it's done.
+#[cfg(feature = "double-buffered")]
+struct Buffers {
+    front: demos::Framebuffer<'static, Format>,
+    back: demos::Framebuffer<'static, Format>,
+}
+
+impl Buffers {
+    #[cfg(not(feature = "double-buffered"))]
+    fn take() -> Self {
+        Buffers { front: demos::take_framebuffer!(Format, FB_W, FB_H).unwrap() }
+    }
+
+    #[cfg(feature = "double-buffered")]
+    fn take() -> Self {
+        Buffers { front: demos::take_framebuffer!(Format, FB_W, FB_H).unwrap(), back: demos::take_framebuffer!(Format, FB_W, FB_H).unwrap() }
+    }
+
+    /// Renders the next frame and has the LTDC show it, returning once the
+    /// beam has finished with the last frame and is in vertical blanking.
+    #[cfg(not(feature = "double-buffered"))]
+    fn render(&mut self, state: &mut demos::Registry, context: &mut ContextS, clut: &mut demos::Clut) {
+        use demos::Demo;
+        state.render(context, clut, &mut self.front);
+        context.wait_for_line(FB_H-1);
+    }
+
+    #[cfg(feature = "double-buffered")]
+    fn render(&mut self, state: &mut demos::Registry, context: &mut ContextS, clut: &mut demos::Clut) {
+        use demos::Demo;
+        state.render(context, clut, &mut self.back);
+        // the shadow register is reloaded when vertical blanking next starts
+        context.ltdc.layer1.cfbar.write(|w| { w.cfbadd().bits(self.back.as_ptr() as u32) });
+        context.ltdc.srcr.write(|w| { w.vbr().reload() });
+        while context.ltdc.srcr.read().vbr().is_reload() { }
+        core::mem::swap(&mut self.front, &mut self.back);
+    }
+}
 
 #[derive(Copy, Clone)]
 enum LTDCState {
@@ -48,8 +137,36 @@ enum LTDCState {
     Initialised,
 }
 
+/// Lines written after the beam had already read them out, and frames whose
+/// rendering ran into the next refresh. Read them with a debugger.
+static MISSED_LINES: AtomicU32 = AtomicU32::new(0);
+static MISSED_FRAMES: AtomicU32 = AtomicU32::new(0);
+
 static LTDC_STATE: Mutex<RefCell<LTDCState>> = Mutex::new(RefCell::new(LTDCState::Uninitialised));
 
+/// The blue user button (B_USER, PI11), polled once per frame from the LTDC
+/// interrupt. A short press switches to the next demo; holding it down for
+/// about half a second switches to the previous one.
+struct UserButton {
+    gpioi: GPIOI,
+    held_frames: u32,
+}
+
+const BUTTON_HOLD_FRAMES: u32 = 18;
+
+impl UserButton {
+    /// Returns the number of demos to step by: 1, -1 or 0.
+    fn poll(&mut self) -> isize {
+        if self.gpioi.idr.read().idr11().bit_is_set() {
+            self.held_frames += 1;
+            if self.held_frames == BUTTON_HOLD_FRAMES { -1 } else { 0 }
+        } else {
+            let held_frames = core::mem::replace(&mut self.held_frames, 0);
+            if held_frames > 0 && held_frames < BUTTON_HOLD_FRAMES { 1 } else { 0 }
+        }
+    }
+}
+
 #[entry]
 fn main() -> ! {
     let _cp = cortex_m::Peripherals::take().unwrap();
@@ -169,6 +286,7 @@ fn main() -> ! {
         // LCD_SCL    PH7        (not hooked up yet)
         // LCD_SDA    PH8        (not hooked up yet)
         // LCD_BLCTRL PK3
+        // B_USER     PI11       (input, polled from the LTDC interrupt)
 
         // Step 1: First, enable the LTDC controlled pins.
         //
@@ -396,7 +514,9 @@ fn main() -> ! {
         gpiok.bsrr.write(|w| { w.bs3().bit(true) });
 
         *GLTDC.borrow(cs).borrow_mut() = Some(ltdc);
-        *GSTATE.borrow(cs).borrow_mut() = Some(demos::Julia::new());
+        *GSTATE.borrow(cs).borrow_mut() = Some(demos::Registry::new());
+        *GFB.borrow(cs).borrow_mut() = Some(Buffers::take());
+        *GBUTTON.borrow(cs).borrow_mut() = Some(UserButton { gpioi, held_frames: 0 });
         unsafe { NVIC::unmask(Interrupt::LTDC); }
     });
 
@@ -409,18 +529,23 @@ struct ContextS<'a> {
     ltdc: &'a mut LTDC,
 }
 
+const ACTIVE_START: i32 = (LTDC_INFO.vsync + LTDC_INFO.vbp) as i32;
+const TOTAL_LINES: i32 = (LTDC_INFO.vsync + LTDC_INFO.vbp + LTDC_INFO.ah + LTDC_INFO.vfp) as i32;
+
 impl<'a> ContextS<'a> {
+    /// Either the beam hasn't passed the line yet, so wait for it, or the
+    /// line's deadline has already passed, so record the miss and let the
+    /// caller write it late rather than wait a whole refresh.
     #[cold]
     fn wait_for_line_cold(&mut self, pixel_y: usize) {
         loop {
-            if self.ltdc.cpsr.read().cypos().bits() > LTDC_INFO.vsync + LTDC_INFO.vbp + pixel_y as u16 {
+            let lines_left = self.lines_left(pixel_y);
+            if lines_left < 0 {
+                MISSED_LINES.fetch_add(1, Ordering::Relaxed);
                 break;
             }
-            if self.ltdc.isr.read().lif().is_reached() {
-                for pixel_x in 0..FB_W {
-                    demos::fb()[pixel_y * FB_W + pixel_x] = 222;
-                }
-                panic!("Timed out on line {}", pixel_y);
+            if lines_left < TOTAL_LINES {
+                break;
             }
         }
     }
@@ -429,11 +554,31 @@ impl<'a> ContextS<'a> {
 impl<'a> demos::Context for ContextS<'a> {
     #[inline(always)]
     fn wait_for_line(&mut self, pixel_y: usize) {
-        if self.ltdc.cpsr.read().cypos().bits() <= LTDC_INFO.vsync + LTDC_INFO.vbp + pixel_y as u16 {
+        if DOUBLE_BUFFERED {
+            return;
+        }
+        let lines_left = self.lines_left(pixel_y);
+        if !(0..TOTAL_LINES).contains(&lines_left) {
             self.wait_for_line_cold(pixel_y);
         }
     }
     #[inline(always)]
+    fn lines_left(&mut self, pixel_y: usize) -> i32 {
+        if DOUBLE_BUFFERED {
+            return i32::MAX;
+        }
+        let cypos = self.ltdc.cpsr.read().cypos().bits() as i32;
+        let line = ACTIVE_START + pixel_y as i32;
+        // The line interrupt that started this render was cleared on entry,
+        // so the flag (or the beam being above the active area) means the
+        // beam has moved on to the next refresh.
+        if self.ltdc.isr.read().lif().is_reached() || cypos < ACTIVE_START {
+            line - cypos
+        } else {
+            TOTAL_LINES - cypos + line
+        }
+    }
+    #[inline(always)]
     fn set_lut(&mut self, i: u8, r: u8, g: u8, b: u8) {
         self.ltdc.layer1.clutwr.write(|w| { w.clutadd().bits(i as u8).red().bits(r as u8).green().bits(g as u8).blue().bits(b as u8) });
     }
@@ -445,6 +590,9 @@ impl<'a> demos::Context for ContextS<'a> {
     fn stats_count_divs(&mut self, _: usize) {}
     fn stats_count_fcvts(&mut self, _: usize) {}
     fn stats_count_fmuls(&mut self, _: usize) {}
+    fn stats_count_computed(&mut self, _: usize) {}
+    fn stats_count_interpolated(&mut self, _: usize) {}
+    fn stats_count_saved_iters(&mut self, _: usize) {}
 }
 
 #[interrupt]
@@ -456,6 +604,10 @@ fn LTDC() {
 
         let mut state_ = GSTATE.borrow(cs).borrow_mut();
         let state = state_.as_mut().unwrap();
+        let mut clut = GCLUT.borrow(cs).borrow_mut();
+        let mut fb_ = GFB.borrow(cs).borrow_mut();
+        let buffers = fb_.as_mut().unwrap();
+        let fb = &buffers.front;
 
         match cortex_m::interrupt::free(|cs| *(LTDC_STATE.borrow(cs).borrow())) {
             LTDCState::Uninitialised => {
@@ -469,17 +621,18 @@ fn LTDC() {
                 ltdc.layer1.whpcr.write(|w| { w.whstpos().bits(LTDC_INFO.hsync + LTDC_INFO.hbp).whsppos().bits(LTDC_INFO.hsync + LTDC_INFO.hbp + LTDC_INFO.aw - 1) });
                 ltdc.layer1.wvpcr.write(|w| { w.wvstpos().bits(LTDC_INFO.vsync + LTDC_INFO.vbp).wvsppos().bits(LTDC_INFO.vsync + LTDC_INFO.vbp + LTDC_INFO.ah - 1) });
                 // format
-                // TODO: make enumerated values
-                ltdc.layer1.pfcr.write(|w| { w.pf().l8() });
+                ltdc.layer1.pfcr.write(|w| { w.pf().variant(Format::PF) });
                 // framebuffer
-                ltdc.layer1.cfbar.write(|w| { w.cfbadd().bits(&*demos::fb() as *const u8 as u32) });
-                // line length, pitch
-                ltdc.layer1.cfblr.write(|w| { w.cfbll().bits((FB_W + 3).try_into().unwrap()).cfbp().bits(FB_W.try_into().unwrap()) });
+                ltdc.layer1.cfbar.write(|w| { w.cfbadd().bits(fb.as_ptr() as u32) });
+                // line length, pitch, both in bytes
+                let line_bytes = fb.width() * core::mem::size_of::<<Format as demos::PixelFormat>::Pixel>();
+                ltdc.layer1.cfblr.write(|w| { w.cfbll().bits((line_bytes + 3).try_into().unwrap()).cfbp().bits(fb.stride_bytes().try_into().unwrap()) });
                 // number of lines
-                ltdc.layer1.cfblnr.write(|w| { w.cfblnbr().bits(FB_H.try_into().unwrap()) });
+                ltdc.layer1.cfblnr.write(|w| { w.cfblnbr().bits(fb.height().try_into().unwrap()) });
                 // blending mode
                 ltdc.layer1.bfcr.write(|w| { w.bf1().constant().bf2().constant() });
-                ltdc.layer1.cr.write(|w| { w.len().enabled().cluten().enabled() });
+                // direct-colour formats leave the CLUT out
+                ltdc.layer1.cr.write(|w| { w.len().enabled().cluten().bit(<Format as demos::PixelFormat>::LUT_ENTRIES != 0) });
 
                 // reload shadow registers immediately
                 ltdc.srcr.write(|w| { w.imr().reload() });
@@ -490,18 +643,26 @@ fn LTDC() {
                 {
                     let mut context = ContextS { ltdc };
                     use demos::Demo;
-                    state.pre_render(&mut context);
+                    Demo::<Format>::pre_render(state, &mut context, &mut clut);
                 }
             },
             LTDCState::Initialised => {
+                let mut button_ = GBUTTON.borrow(cs).borrow_mut();
+                let button = button_.as_mut().unwrap();
+
                 let mut context = ContextS { ltdc };
                 use demos::Demo;
-                state.render(&mut context);
-                context.wait_for_line(FB_H-1);
-                state.pre_render(&mut context);
+                buffers.render(state, &mut context, &mut clut);
+                match button.poll() {
+                    1 => state.next::<Format>(&mut context, &mut clut),
+                    -1 => state.prev::<Format>(&mut context, &mut clut),
+                    _ => Demo::<Format>::pre_render(state, &mut context, &mut clut),
+                }
             },
         }
-        #[cfg(not(debug_assertions))]
-        assert!(!ltdc.isr.read().lif().bit());
+        // double-buffered frames can take as many refreshes as they like
+        if !DOUBLE_BUFFERED && ltdc.isr.read().lif().bit() {
+            MISSED_FRAMES.fetch_add(1, Ordering::Relaxed);
+        }
     });
 }